@@ -1,6 +1,7 @@
 use signaler_protocol::{
-    ChatMessage, ChatRoomCommand, Credentials, Participant, RoomEvent, RoomId, SessionCommand, SessionDescription,
-    SessionMessage, UserProfile,
+    Access, ChatMessage, ChatRoomCommand, Credentials, Ephemeral, ErrorCode, IceCandidate, MyRoom, Participant,
+    Presence, Reaction, Request, Response, Role, RoomEvent, RoomId, RoomInfo, RoomSettings, SessionCommand,
+    SessionDescription, SessionMessage, UserProfile,
 };
 use typescript_definitions::TypeScriptifyTrait;
 
@@ -11,10 +12,23 @@ fn main() {
     println!("{}", Credentials::type_script_ify());
     println!("{}", UserProfile::type_script_ify());
     println!("{}", ChatMessage::type_script_ify());
+    println!("{}", Reaction::type_script_ify());
+    println!("{}", Presence::type_script_ify());
+    println!("{}", Role::type_script_ify());
     println!("{}", Participant::type_script_ify());
+    println!("{}", RoomInfo::type_script_ify());
+    println!("{}", MyRoom::type_script_ify());
+    println!("{}", IceCandidate::type_script_ify());
+    println!("{}", Access::type_script_ify());
+    println!("{}", RoomSettings::type_script_ify());
+    println!("{}", Ephemeral::type_script_ify());
     println!("{}", RoomEvent::type_script_ify());
     println!("{}", SessionDescription::type_script_ify());
+    println!("export type RequestId = number;");
+    println!("{}", Request::<SessionCommand>::type_script_ify());
+    println!("{}", Response::<SessionMessage>::type_script_ify());
     println!("{}", SessionCommand::type_script_ify());
+    println!("{}", ErrorCode::type_script_ify());
     println!("{}", SessionMessage::type_script_ify());
     println!("{}", ChatRoomCommand::type_script_ify());
 }
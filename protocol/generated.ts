@@ -2,40 +2,154 @@ export type RoomId = string;
 export type SessionId = String;
 export type Credentials = 
  | { type: "usernamePassword"; username: string; password: string } 
- | { type: "adHoc"; username: string };
+ | { type: "adHoc"; username: string } 
+ | { type: "token"; token: Uuid };
 export type UserProfile = { fullName: string };
 // Actual chat Message
 // is send via `SessionCommand::Message` and received via `SessionMessage::Message`
-export type ChatMessage = { content: string; sender: SessionId; sent: string; uuid: Uuid };
+export type ChatMessage = { content: string; sender: SessionId; sent: string; uuid: Uuid; edited: string | null; reactions: Reaction []; replyTo: Uuid | null; thread: Uuid | null };
+// Everybody who reacted to a message with the same emoji
+// is part of `ChatMessage` and received via `SessionMessage::Reactions`
+export type Reaction = { emoji: string; reactors: SessionId [] };
+// Whether a user is around
+// is sent via `SessionCommand::SetPresence` and received via `SessionMessage::Presence`
+export type Presence = 
+ | { type: "online" } 
+ | { type: "away" } 
+ | { type: "busy" } 
+ | { type: "custom"; text: string } 
+ | { type: "offline" };
+// What a participant may do in a room, ordered by privilege
+export type Role = 
+ | "member" 
+ | "moderator" 
+ | "owner";
 // SessionId and Full Name
 // is send via `SessionCommand::Message` and received via `SessionMessage::Message`
-export type Participant = { fullName: string; sessionId: SessionId };
+export type Participant = { fullName: string; sessionId: SessionId; role: Role };
+// Room name and how many participants it has
+// is received via `SessionMessage::RoomList`
+export type RoomInfo = { room: RoomId; participants: number };
+// A room I'm member of and how many of its messages I haven't read
+// is received via `SessionMessage::MyRoomList`
+export type MyRoom = { room: RoomId; unread: number };
+// WebRTC ICE candidate, as in `RTCIceCandidateInit`
+// is sent via `ChatRoomCommand::IceCandidate` and received via `SessionMessage::IceCandidate`
+export type IceCandidate = { candidate: string; sdpMid: string | null; sdpMLineIndex: number | null };
+// Who may join a room
+export type Access = 
+ | { type: "public" } 
+ | { type: "password"; password: string } 
+ | { type: "inviteOnly"; invited: string [] } 
+ | { type: "private" };
+// How a room behaves
+// is sent via `SessionCommand::CreateRoom`
+export type RoomSettings = { access: Access; maxParticipants: number | null; waitlist: boolean };
+// Short lived state of a participant, broadcast but never stored
+// expires unless it is sent again every few seconds
+export type Ephemeral = 
+ | { type: "typing" } 
+ | { type: "speaking" } 
+ | { type: "cursor"; position: Value };
 export type RoomEvent = 
  | { participantJoined: { name: string } } 
- | { participantLeft: { name: string } };
+ | { participantLeft: { name: string } } 
+ | { participantKicked: { name: string } } 
+ | { participantBanned: { name: string } } 
+ | { participantMuted: { name: string } } 
+ | { participantUnmuted: { name: string } } 
+ | { roleChanged: { name: string; role: Role } };
 export type SessionDescription = { sessionId: SessionId };
+export type RequestId = number;
+// Optional envelope around a command
+// every direct reply to it is wrapped in a `Response` with the same `id`
+export type Request<C> = { id: RequestId; command: C };
+// Envelope around a direct reply to a `Request`
+export type Response<M> = { id: RequestId; message: M };
 // Command sent to the server
 export type SessionCommand = 
- | { type: "join"; room: RoomId } 
+ | { type: "join"; room: RoomId; password: string | null } 
+ | { type: "createRoom"; room: RoomId; settings: RoomSettings } 
  | { type: "chatRoom"; room: RoomId; command: ChatRoomCommand } 
  | { type: "listRooms" } 
  | { type: "listMyRooms" } 
  | { type: "shutDown" } 
+ | { type: "direct"; to: SessionId; payload: Value } 
+ | { type: "closeRoom"; room: RoomId } 
+ | { type: "setPresence"; presence: Presence } 
+ | { type: "subscribePresence"; username: string } 
+ | { type: "unsubscribePresence"; username: string } 
  | { type: "authenticate"; credentials: Credentials };
+// What went wrong
+// is received via `SessionMessage::Error`
+export type ErrorCode = 
+ | "invalidCommand" 
+ | "unsupportedCommand" 
+ | "notAuthenticated" 
+ | "sessionGone" 
+ | "notAMember" 
+ | "roomNotFound" 
+ | "forbidden" 
+ | "roomExists" 
+ | "roomFull" 
+ | "messageNotFound" 
+ | "peerNotFound" 
+ | "internal";
 // Message received from the server
 export type SessionMessage = 
  | { type: "welcome"; session: SessionDescription } 
- | { type: "authenticated" } 
+ | { type: "authenticated"; token: Uuid } 
+ | { type: "authenticationFailed" } 
  | { type: "profile"; profile: UserProfile } 
- | { type: "roomList"; rooms: string [] } 
- | { type: "myRoomList"; rooms: string [] } 
+ | { type: "roomList"; rooms: RoomInfo [] } 
+ | { type: "myRoomList"; rooms: MyRoom [] } 
  | { type: "roomParticipants"; room: RoomId; participants: Participant [] } 
  | { type: "roomEvent"; room: RoomId; event: RoomEvent } 
+ | { type: "left"; room: RoomId } 
+ | { type: "roomClosed"; room: RoomId } 
+ | { type: "roomCreated"; room: RoomId } 
+ | { type: "joinDeclined"; room: RoomId; reason: string } 
+ | { type: "waitlisted"; room: RoomId; position: number } 
+ | { type: "kicked"; room: RoomId } 
  | { type: "message"; message: ChatMessage; room: RoomId } 
+ | { type: "messageEdited"; message: ChatMessage; room: RoomId } 
+ | { type: "messageDeleted"; uuid: Uuid; room: RoomId } 
+ | { type: "ephemeral"; room: RoomId; from: SessionId; signal: Ephemeral; active: boolean } 
+ | { type: "reactions"; uuid: Uuid; room: RoomId; reactions: Reaction [] } 
+ | { type: "readReceipt"; room: RoomId; uuid: Uuid; reader: SessionId } 
+ | { type: "history"; room: RoomId; messages: ChatMessage [] } 
+ | { type: "thread"; room: RoomId; root: Uuid; messages: ChatMessage [] } 
+ | { type: "offer"; room: RoomId; from: SessionId; sdp: string } 
+ | { type: "answer"; room: RoomId; from: SessionId; sdp: string } 
+ | { type: "iceCandidate"; room: RoomId; from: SessionId; candidate: IceCandidate } 
+ | { type: "hangup"; room: RoomId; from: SessionId } 
+ | { type: "direct"; from: SessionId; payload: Value } 
+ | { type: "delivered"; to: SessionId } 
+ | { type: "undelivered"; to: SessionId } 
+ | { type: "presence"; username: string; session: SessionId; presence: Presence } 
+ | { type: "goingAway"; reason: string } 
  | { type: "any"; payload: Value } 
- | { type: "error"; message: string };
+ | { type: "error"; code: ErrorCode; message: string };
 // Command sent to the server
 export type ChatRoomCommand = 
  | { type: "leave" } 
- | { type: "message"; content: string } 
- | { type: "listParticipants" };
+ | { type: "message"; content: string; replyTo: Uuid | null } 
+ | { type: "listParticipants" } 
+ | { type: "history"; before: Uuid | null; limit: number } 
+ | { type: "thread"; root: Uuid } 
+ | { type: "ephemeral"; signal: Ephemeral; active: boolean } 
+ | { type: "offer"; to: SessionId; sdp: string } 
+ | { type: "answer"; to: SessionId; sdp: string } 
+ | { type: "iceCandidate"; to: SessionId; candidate: IceCandidate } 
+ | { type: "hangup"; to: SessionId } 
+ | { type: "kick"; participant: SessionId } 
+ | { type: "ban"; participant: SessionId } 
+ | { type: "mute"; participant: SessionId } 
+ | { type: "unmute"; participant: SessionId } 
+ | { type: "setRole"; participant: SessionId; role: Role } 
+ | { type: "invite"; username: string } 
+ | { type: "edit"; uuid: Uuid; content: string } 
+ | { type: "delete"; uuid: Uuid } 
+ | { type: "react"; uuid: Uuid; emoji: string } 
+ | { type: "unreact"; uuid: Uuid; emoji: string } 
+ | { type: "markRead"; upTo: Uuid; receipt: boolean };
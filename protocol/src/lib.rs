@@ -9,7 +9,7 @@ use uuid::Uuid;
 #[cfg(target_arch = "wasm32")]
 extern crate wasm_bindgen;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SessionId(Uuid);
 
 impl From<Uuid> for SessionId {
@@ -18,6 +18,12 @@ impl From<Uuid> for SessionId {
     }
 }
 
+impl From<SessionId> for Uuid {
+    fn from(SessionId(inner): SessionId) -> Self {
+        inner
+    }
+}
+
 #[derive(Clone, Hash, PartialEq, Eq, Debug, Serialize, Deserialize, TypeScriptify)]
 pub struct RoomId(String);
 
@@ -55,7 +61,9 @@ pub enum Credentials {
 
     /// Even simpler Authentication Credentials
     AdHoc { username: String },
-    // TODO: Token { token: Uuid }
+
+    /// Resume an existing session with the token received in `SessionMessage::Authenticated`
+    Token { token: Uuid },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, TypeScriptify)]
@@ -74,6 +82,21 @@ pub struct ChatMessage {
     pub sender: SessionId,
     pub sent: chrono::DateTime<chrono::Utc>,
     pub uuid: Uuid,
+
+    /// when `content` was last changed via `ChatRoomCommand::Edit`
+    #[serde(default)]
+    pub edited: Option<chrono::DateTime<chrono::Utc>>,
+
+    #[serde(default)]
+    pub reactions: Vec<Reaction>,
+
+    /// the message this one replies to
+    #[serde(default)]
+    pub reply_to: Option<Uuid>,
+
+    /// the first message of the thread this one is part of
+    #[serde(default)]
+    pub thread: Option<Uuid>,
 }
 
 impl ChatMessage {
@@ -83,10 +106,55 @@ impl ChatMessage {
             sender,
             sent: chrono::Utc::now(),
             uuid: Uuid::new_v4(),
+            edited: None,
+            reactions: Vec::new(),
+            reply_to: None,
+            thread: None,
         }
     }
 }
 
+/// Everybody who reacted to a message with the same emoji
+///
+/// is part of `ChatMessage` and received via `SessionMessage::Reactions`
+#[derive(Clone, Debug, Serialize, Deserialize, TypeScriptify)]
+#[serde(rename_all = "camelCase")]
+pub struct Reaction {
+    pub emoji: String,
+    pub reactors: Vec<SessionId>,
+}
+
+/// Whether a user is around
+///
+/// is sent via `SessionCommand::SetPresence` and received via `SessionMessage::Presence`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, TypeScriptify)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Presence {
+    #[default]
+    Online,
+    Away,
+    Busy,
+    Custom {
+        text: String,
+    },
+
+    /// set by the server when the connection is gone
+    Offline,
+}
+
+/// What a participant may do in a room, ordered by privilege
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, TypeScriptify)]
+#[serde(rename_all = "camelCase")]
+pub enum Role {
+    Member,
+
+    /// may kick, ban and mute members
+    Moderator,
+
+    /// created the room, may also change roles
+    Owner,
+}
+
 /// SessionId and Full Name
 ///
 /// is send via `SessionCommand::Message` and received via `SessionMessage::Message`
@@ -95,22 +163,107 @@ impl ChatMessage {
 pub struct Participant {
     pub full_name: String,
     pub session_id: SessionId,
+    pub role: Role,
 }
 
-impl From<(UserProfile, SessionId)> for Participant {
-    fn from((profile, session_id): (UserProfile, SessionId)) -> Participant {
+impl From<(UserProfile, SessionId, Role)> for Participant {
+    fn from((profile, session_id, role): (UserProfile, SessionId, Role)) -> Participant {
         Participant {
             full_name: profile.full_name,
             session_id,
+            role,
         }
     }
 }
 
+/// Room name and how many participants it has
+///
+/// is received via `SessionMessage::RoomList`
+#[derive(Clone, Debug, Serialize, Deserialize, TypeScriptify)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomInfo {
+    pub room: RoomId,
+    pub participants: usize,
+}
+
+/// A room I'm member of and how many of its messages I haven't read
+///
+/// is received via `SessionMessage::MyRoomList`
+#[derive(Clone, Debug, Serialize, Deserialize, TypeScriptify)]
+#[serde(rename_all = "camelCase")]
+pub struct MyRoom {
+    pub room: RoomId,
+    pub unread: usize,
+}
+
+/// WebRTC ICE candidate, as in `RTCIceCandidateInit`
+///
+/// is sent via `ChatRoomCommand::IceCandidate` and received via `SessionMessage::IceCandidate`
+#[derive(Clone, Debug, Serialize, Deserialize, TypeScriptify)]
+#[serde(rename_all = "camelCase")]
+pub struct IceCandidate {
+    pub candidate: String,
+    pub sdp_mid: Option<String>,
+    pub sdp_m_line_index: Option<u16>,
+}
+
+/// Who may join a room
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypeScriptify)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Access {
+    /// anybody may join
+    #[default]
+    Public,
+
+    /// joining requires the password
+    Password { password: String },
+
+    /// only invited users may join
+    InviteOnly { invited: Vec<String> },
+
+    /// anybody who knows the name may join, but the room is not listed
+    Private,
+}
+
+/// How a room behaves
+///
+/// is sent via `SessionCommand::CreateRoom`
+#[derive(Clone, Debug, Default, Serialize, Deserialize, TypeScriptify)]
+#[serde(rename_all = "camelCase")]
+pub struct RoomSettings {
+    #[serde(default)]
+    pub access: Access,
+
+    /// falls back to the server default if unset
+    #[serde(default)]
+    pub max_participants: Option<usize>,
+
+    /// queue joins while the room is full instead of rejecting them
+    #[serde(default)]
+    pub waitlist: bool,
+}
+
+/// Short lived state of a participant, broadcast but never stored
+///
+/// expires unless it is sent again every few seconds
+#[derive(Clone, Debug, Serialize, Deserialize, TypeScriptify)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Ephemeral {
+    Typing,
+    Speaking,
+    Cursor { position: serde_json::Value },
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, TypeScriptify)]
 #[serde(rename_all = "camelCase")]
 pub enum RoomEvent {
     ParticipantJoined { name: String },
     ParticipantLeft { name: String },
+    ParticipantKicked { name: String },
+    ParticipantBanned { name: String },
+    ParticipantMuted { name: String },
+    ParticipantUnmuted { name: String },
+    RoleChanged { name: String, role: Role },
 }
 
 /// Command sent to the server
@@ -132,8 +285,11 @@ pub struct SessionDescription {
 #[serde(rename_all = "camelCase", tag = "type")]
 #[rustfmt::skip]
 pub enum SessionCommand {
-    /// Join a particular room
-    Join { room: RoomId },
+    /// Join a particular room, it is created if it does not exist yet
+    Join { room: RoomId, password: Option<String> },
+
+    /// Create a room with particular settings and join it
+    CreateRoom { room: RoomId, settings: RoomSettings },
 
     /// Send a message to all participants of that room
     ChatRoom { room: RoomId, command: ChatRoomCommand },
@@ -147,6 +303,20 @@ pub enum SessionCommand {
     /// shutdown server 😈
     ShutDown,
 
+    /// Send `payload` to another session, outside of any room
+    Direct { to: SessionId, payload: serde_json::Value },
+
+    /// Close a room, only its owner or an admin may do this
+    CloseRoom { room: RoomId },
+
+    /// Tell room members and subscribers whether you are around
+    SetPresence { presence: Presence },
+
+    /// Receive presence updates of a user
+    SubscribePresence { username: String },
+
+    UnsubscribePresence { username: String },
+
     /// Request Authentication Token
     Authenticate { credentials: Credentials },
 }
@@ -159,10 +329,63 @@ pub enum ChatRoomCommand {
     /// Leave a particular room
     Leave,
 
-    /// Send a message to all participants of that room
-    Message { content: String },
+    /// Send a message to all participants of that room, optionally as a reply to another message
+    Message { content: String, #[serde(rename = "replyTo")] reply_to: Option<Uuid> },
 
     ListParticipants,
+
+    /// Request up to `limit` messages sent before `before`, or the latest ones
+    History { before: Option<Uuid>, limit: usize },
+
+    /// Request the thread started by `root`
+    Thread { root: Uuid },
+
+    /// Start, refresh or stop an ephemeral signal like typing
+    Ephemeral { signal: Ephemeral, active: bool },
+
+    /// Send a WebRTC offer to another participant of that room
+    Offer { to: SessionId, sdp: String },
+
+    /// Answer a WebRTC offer of another participant of that room
+    Answer { to: SessionId, sdp: String },
+
+    /// Send an ICE candidate to another participant of that room
+    IceCandidate { to: SessionId, candidate: IceCandidate },
+
+    /// End the call with another participant of that room
+    Hangup { to: SessionId },
+
+    /// Remove a participant, requires outranking them
+    Kick { participant: SessionId },
+
+    /// Remove a participant and keep their user from joining again, requires outranking them
+    Ban { participant: SessionId },
+
+    /// Keep a participant from sending messages, requires outranking them
+    Mute { participant: SessionId },
+
+    Unmute { participant: SessionId },
+
+    /// Change the role of a participant, only the owner may do this
+    SetRole { participant: SessionId, role: Role },
+
+    /// Allow a user to join an invite only room, requires being at least a moderator
+    Invite { username: String },
+
+    /// Change the content of a message you sent
+    Edit { uuid: Uuid, content: String },
+
+    /// Remove a message you sent
+    Delete { uuid: Uuid },
+
+    /// React to a message with an emoji
+    React { uuid: Uuid, emoji: String },
+
+    /// Take back a reaction
+    Unreact { uuid: Uuid, emoji: String },
+
+    /// Mark all messages up to `up_to` as read, optionally telling its sender
+    MarkRead { #[serde(rename = "upTo")] up_to: Uuid, #[serde(default)] receipt: bool },
 }
 
 impl SessionCommand {
@@ -170,7 +393,10 @@ impl SessionCommand {
         use SessionCommand::*;
         let room = "roomName";
         serde_json::to_string_pretty(&[
-            Join { room: room.into() },
+            Join {
+                room: room.into(),
+                password: None,
+            },
             Authenticate {
                 credentials: Credentials::UsernamePassword {
                     username: "username".into(),
@@ -185,12 +411,76 @@ impl SessionCommand {
     }
 }
 
+/// Chosen by the client to match replies to requests
+pub type RequestId = u64;
+
+/// Optional envelope around a command
+///
+/// every direct reply to it is wrapped in a `Response` with the same `id`
+#[derive(Debug, Serialize, Deserialize, TypeScriptify)]
+#[serde(rename_all = "camelCase")]
+pub struct Request<C> {
+    pub id: RequestId,
+    pub command: C,
+}
+
+/// Envelope around a direct reply to a `Request`
+#[derive(Debug, Serialize, Deserialize, TypeScriptify)]
+#[serde(rename_all = "camelCase")]
+pub struct Response<M> {
+    pub id: RequestId,
+    pub message: M,
+}
+
 pub trait SessionCommandDispatcher {
     type Context;
 
     fn dispatch_command(&self, msg: SessionCommand, ctx: &mut Self::Context);
 }
 
+/// What went wrong
+///
+/// is received via `SessionMessage::Error`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TypeScriptify)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    /// the command could not be parsed
+    InvalidCommand,
+
+    /// the command is not supported in this state
+    UnsupportedCommand,
+
+    /// the command requires authentication
+    NotAuthenticated,
+
+    /// the session has been stopped, authenticate again
+    SessionGone,
+
+    /// the command addresses a room the session is not a member of
+    NotAMember,
+
+    /// the room does not exist (anymore)
+    RoomNotFound,
+
+    /// the session is not allowed to do this
+    Forbidden,
+
+    /// a room with that name exists already
+    RoomExists,
+
+    /// the room has reached its maximum number of participants
+    RoomFull,
+
+    /// the message does not exist (anymore)
+    MessageNotFound,
+
+    /// the addressed session is not reachable (anymore)
+    PeerNotFound,
+
+    /// something went wrong on the server
+    Internal,
+}
+
 /// Message received from the server
 #[derive(Debug, Serialize, Deserialize, TypeScriptify)]
 #[serde(rename_all = "camelCase", tag = "type")]
@@ -199,33 +489,115 @@ pub enum SessionMessage {
     Welcome { session: SessionDescription },
 
     /// response to `SessionCommand::Authenticate`
-    Authenticated,
+    ///
+    /// `token` can be used to resume this session via `Credentials::Token` after the connection dropped
+    Authenticated { token: Uuid },
+
+    /// response to `SessionCommand::Authenticate` if the credentials were rejected
+    AuthenticationFailed,
 
     Profile { profile: UserProfile },
 
-    RoomList { rooms: Vec<String> },
+    RoomList { rooms: Vec<RoomInfo> },
 
-    MyRoomList { rooms: Vec<String> },
+    MyRoomList { rooms: Vec<MyRoom> },
 
     RoomParticipants { room: RoomId, participants: Vec<Participant> },
     RoomEvent { room: RoomId, event: RoomEvent },
 
+    /// response to `ChatRoomCommand::Leave`
+    Left { room: RoomId },
+
+    /// the room has been closed, response to `SessionCommand::CloseRoom`
+    RoomClosed { room: RoomId },
+
+    /// response to `SessionCommand::CreateRoom`
+    RoomCreated { room: RoomId },
+
+    /// response to `SessionCommand::Join` if access to the room was refused
+    JoinDeclined { room: RoomId, reason: String },
+
+    /// response to `SessionCommand::Join` if the room is full, the session joins once a spot is free
+    Waitlisted { room: RoomId, position: usize },
+
+    /// a moderator removed this session from the room
+    Kicked { room: RoomId },
+
     Message { message: ChatMessage, room: RoomId },
 
+    /// a message in `room` was edited by its sender
+    MessageEdited { message: ChatMessage, room: RoomId },
+
+    /// a message in `room` was deleted by its sender
+    MessageDeleted { uuid: Uuid, room: RoomId },
+
+    /// another participant of `room` started or stopped an ephemeral signal
+    Ephemeral { room: RoomId, from: SessionId, signal: Ephemeral, active: bool },
+
+    /// the reactions to a message in `room` changed
+    Reactions { uuid: Uuid, room: RoomId, reactions: Vec<Reaction> },
+
+    /// `reader` has read your message `uuid` in `room`
+    ReadReceipt { room: RoomId, uuid: Uuid, reader: SessionId },
+
+    /// older messages of a room, oldest first
+    History { room: RoomId, messages: Vec<ChatMessage> },
+
+    /// response to `ChatRoomCommand::Thread`, oldest first
+    Thread { room: RoomId, root: Uuid, messages: Vec<ChatMessage> },
+
+    /// WebRTC offer relayed from another participant of `room`
+    Offer { room: RoomId, from: SessionId, sdp: String },
+
+    /// WebRTC answer relayed from another participant of `room`
+    Answer { room: RoomId, from: SessionId, sdp: String },
+
+    /// ICE candidate relayed from another participant of `room`
+    IceCandidate { room: RoomId, from: SessionId, candidate: IceCandidate },
+
+    /// another participant of `room` ended the call
+    Hangup { room: RoomId, from: SessionId },
+
+    /// payload sent directly by another session via `SessionCommand::Direct`
+    Direct { from: SessionId, payload: serde_json::Value },
+
+    /// response to `SessionCommand::Direct` if `to` received the payload
+    Delivered { to: SessionId },
+
+    /// response to `SessionCommand::Direct` if `to` does not exist (anymore)
+    Undelivered { to: SessionId },
+
+    /// a session of `username` changed its presence
+    Presence { username: String, session: SessionId, presence: Presence },
+
+    /// the server is shutting down and will close the connection
+    GoingAway { reason: String },
+
     Any { payload: serde_json::Value },
 
-    Error { message: String },
+    Error { code: ErrorCode, message: String },
 }
 
 impl SessionMessage {
-    pub fn err(msg: impl Into<String>) -> Self {
-        SessionMessage::Error { message: msg.into() }
+    pub fn err(code: ErrorCode, msg: impl Into<String>) -> Self {
+        SessionMessage::Error {
+            code,
+            message: msg.into(),
+        }
     }
 
     pub fn into_json(self) -> String {
         serde_json::to_string(&self).unwrap()
     }
 
+    /// wrapped in a `Response` if this answers a `Request`
+    pub fn into_reply_json(self, id: Option<RequestId>) -> String {
+        match id {
+            Some(id) => serde_json::to_string(&Response { id, message: self }).unwrap(),
+            None => self.into_json(),
+        }
+    }
+
     /// dev convenience only!
     pub fn any<T: serde::Serialize>(anything: T) -> Self {
         SessionMessage::Any {
@@ -261,15 +633,38 @@ mod wasm_wrapper {
         let msg: SessionMessage = raw.into_serde().unwrap();
         match msg {
             Welcome { session } => log::debug!("welcome {:?}", session),
-            Authenticated => log::debug!(r"Authenticated \0/"),
+            Authenticated { .. } => log::debug!(r"Authenticated \0/"),
+            AuthenticationFailed => log::debug!("Authentication failed"),
             Profile { profile } => log::debug!("profile: {:?}", profile),
             RoomList { rooms } => log::debug!("RoomsList: {:?}", rooms),
             MyRoomList { rooms } => log::debug!("MyRoomList: {:?}", rooms),
             RoomParticipants { room, participants } => log::debug!("RoomParticipants of {:?}: {:?}", room, participants),
             RoomEvent {room, event } => log::debug!("{room:?} {event:#?}"),
+            Left { room } => log::debug!("Left {room:?}"),
+            RoomClosed { room } => log::debug!("RoomClosed {room:?}"),
+            RoomCreated { room } => log::debug!("RoomCreated {room:?}"),
+            JoinDeclined { room, reason } => log::debug!("JoinDeclined {room:?}: {}", reason),
+            Waitlisted { room, position } => log::debug!("Waitlisted for {room:?} at {}", position),
+            Kicked { room } => log::debug!("Kicked from {room:?}"),
             Message { message, room } => log::debug!( "Message in {room:?} {message:?}", room = room, message = message),
+            MessageEdited { message, room } => log::debug!("MessageEdited in {room:?} {message:?}"),
+            MessageDeleted { uuid, room } => log::debug!("MessageDeleted in {room:?} {uuid}"),
+            Ephemeral { room, from, signal, active } => log::debug!("Ephemeral from {from:?} in {room:?}: {signal:?} {active}"),
+            Reactions { uuid, room, reactions } => log::debug!("Reactions to {uuid} in {room:?}: {reactions:?}"),
+            History { room, messages } => log::debug!("History of {room:?}: {} messages", messages.len()),
+            ReadReceipt { room, uuid, reader } => log::debug!("{reader:?} read {uuid} in {room:?}"),
+            Thread { room, root, messages } => log::debug!("Thread {root} in {room:?}: {} messages", messages.len()),
+            Offer { room, from, .. } => log::debug!("Offer from {from:?} in {room:?}"),
+            Answer { room, from, .. } => log::debug!("Answer from {from:?} in {room:?}"),
+            IceCandidate { room, from, candidate } => log::debug!("IceCandidate from {from:?} in {room:?}: {candidate:?}"),
+            Hangup { room, from } => log::debug!("Hangup from {from:?} in {room:?}"),
+            Direct { from, payload } => log::debug!("Direct from {from:?}: {:#?}", payload),
+            Delivered { to } => log::debug!("Delivered to {to:?}"),
+            Undelivered { to } => log::debug!("Undelivered to {to:?}"),
+            Presence { username, presence, .. } => log::debug!("Presence of {}: {:?}", username, presence),
+            GoingAway { reason } => log::debug!("GoingAway: {}", reason),
             Any { payload } => log::debug!("Any: {:#?}", payload),
-            Error { message } => log::debug!("Error: {}", message),
+            Error { code, message } => log::debug!("Error {:?}: {}", code, message),
         }
     }
 }
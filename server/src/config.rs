@@ -1,11 +1,54 @@
+use std::{path::PathBuf, time::Duration};
+
 #[derive(Debug, serde::Deserialize)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
 }
+
+#[derive(Debug, serde::Deserialize)]
+pub struct CredentialsConfig {
+    pub file: PathBuf,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(default)]
+pub struct RoomsConfig {
+    /// seconds an empty room stays open
+    pub idle_timeout: u64,
+    /// for rooms that don't set their own limit
+    pub max_participants: usize,
+}
+
+impl RoomsConfig {
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout)
+    }
+}
+
+impl Default for RoomsConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: 300,
+            max_participants: 100,
+        }
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct HistoryConfig {
+    /// SQLite database for room histories, they are kept in memory if unset
+    pub database: Option<PathBuf>,
+}
+
 #[derive(Debug, serde::Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
+    pub credentials: CredentialsConfig,
+    #[serde(default)]
+    pub rooms: RoomsConfig,
+    #[serde(default)]
+    pub history: HistoryConfig,
     pub log_config: Option<String>,
 }
 
@@ -1,9 +1,11 @@
 use async_trait::async_trait;
+use futures::SinkExt;
 use hannibal::{Actor, Context, Handler};
+use signaler_protocol::SessionMessage;
 use tracing::log;
 
 use super::Connection;
-use crate::session::message::FromSession;
+use crate::{session::message::FromSession, shutdown::GoingAway};
 
 #[async_trait::async_trait]
 impl Actor for Connection {
@@ -12,6 +14,7 @@ impl Actor for Connection {
 
         if let Some(ws_receiver) = self.ws_receiver.take() {
             ctx.add_stream(ws_receiver);
+            ctx.subscribe::<GoingAway>().await?;
             self.send_welcome().await;
         } else {
             log::error!("unable to take ws_receiver stream");
@@ -19,8 +22,11 @@ impl Actor for Connection {
         }
         Ok(())
     }
-    async fn stopped(&mut self, _ctx: &mut hannibal::Context<Self>) {
+    async fn stopped(&mut self, ctx: &mut hannibal::Context<Self>) {
         log::trace!("shutting down");
+        if let Err(error) = ctx.unsubscribe::<GoingAway>().await {
+            log::warn!("failed to unsubscribe {}", error);
+        }
     }
 }
 
@@ -34,10 +40,26 @@ impl Handler<FromSession> for Connection {
 
                 self.send(&payload).await;
             }
+            FromSession::Reply { id, message } => {
+                self.send(message.into_reply_json(Some(id))).await;
+            }
             FromSession::SessionAssociated { session } => {
                 log::trace!("associated session");
-                self.session = Some(session)
+                self.session = Some(session);
             }
         }
     }
 }
+
+#[async_trait]
+impl Handler<GoingAway> for Connection {
+    async fn handle(&mut self, ctx: &mut Context<Self>, msg: GoingAway) {
+        log::debug!("server is going away, closing connection");
+        self.send(SessionMessage::GoingAway { reason: msg.reason }.into_json())
+            .await;
+        if let Err(error) = self.ws_sender.close().await {
+            log::warn!("failed to close websocket {}", error);
+        }
+        ctx.stop(None);
+    }
+}
@@ -1,3 +1,4 @@
+use signaler_protocol::{ErrorCode, SessionMessage};
 use thiserror::Error;
 
 /// The kind of an error.
@@ -7,9 +8,28 @@ pub enum Error {
     #[error("Session was destroyed already")]
     SessionGone,
 
-    #[error("failed to parse incomming command")]
+    #[error("not authenticated")]
+    NotAuthenticated,
+
+    #[error("failed to parse incomming command: {0}")]
     Parsing(#[from] serde_json::Error),
 
     #[error("badly typed error")]
     Anyhow(#[from] anyhow::Error),
 }
+
+impl Error {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::SessionGone => ErrorCode::SessionGone,
+            Error::NotAuthenticated => ErrorCode::NotAuthenticated,
+            Error::Parsing(_) => ErrorCode::InvalidCommand,
+            Error::Anyhow(_) => ErrorCode::Internal,
+        }
+    }
+
+    /// what the client gets to see
+    pub fn to_session_message(&self) -> SessionMessage {
+        SessionMessage::err(self.code(), self.to_string())
+    }
+}
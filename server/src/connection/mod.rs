@@ -4,11 +4,14 @@ use futures::{
     StreamExt,
 };
 use hannibal::{Context, Service, WeakAddr};
+use serde::de::DeserializeOwned;
 use tracing::log;
 use uuid::Uuid;
 use warp::ws::{Message, WebSocket};
 
-use signaler_protocol::{ConnectionCommand, Credentials, SessionCommand, SessionDescription, SessionMessage};
+use signaler_protocol::{
+    ConnectionCommand, Credentials, Request, RequestId, SessionCommand, SessionDescription, SessionMessage,
+};
 
 use crate::{
     session::{self, Session},
@@ -69,11 +72,15 @@ impl Connection {
 
     async fn handle_incoming_message(&mut self, raw_msg: &str, ctx: &mut Context<Self>) -> Result<(), error::Error> {
         if let Some(session) = self.session.as_ref() {
-            let command = serde_json::from_str::<SessionCommand>(raw_msg)?;
-            session
-                .upgrade()
-                .ok_or(error::Error::SessionGone)?
-                .send(session::command::Command::from(command))?;
+            let (id, command) = parse_request::<SessionCommand>(raw_msg)?;
+            match session.upgrade().filter(|session| !session.stopped()) {
+                Some(session) => session.send(session::command::Command { id, command })?,
+                None => {
+                    // allow authenticating again
+                    self.session = None;
+                    return Err(error::Error::SessionGone);
+                }
+            }
         } else {
             self.handle_connection_message(raw_msg, ctx).await?;
         }
@@ -81,23 +88,41 @@ impl Connection {
     }
 
     async fn handle_connection_message(&mut self, raw_msg: &str, ctx: &mut Context<Self>) -> Result<(), error::Error> {
-        let msg = serde_json::from_str::<ConnectionCommand>(raw_msg)?;
+        let (id, msg) = match parse_request::<ConnectionCommand>(raw_msg) {
+            Ok(request) => request,
+            Err(_) if parse_request::<SessionCommand>(raw_msg).is_ok() => return Err(error::Error::NotAuthenticated),
+            Err(error) => return Err(error.into()),
+        };
         log::trace!("parsed ok {:?}", msg);
         match msg {
-            ConnectionCommand::Authenticate { credentials } => self.associate_session(credentials, ctx).await,
+            ConnectionCommand::Authenticate { credentials } => self.associate_session(id, credentials, ctx).await,
         }
         Ok(())
     }
 
-    async fn associate_session(&mut self, credentials: Credentials, ctx: &mut Context<Self>) {
+    async fn associate_session(&mut self, id: Option<RequestId>, credentials: Credentials, ctx: &mut Context<Self>) {
         log::trace!("trying to get a session");
         let sm = SessionManager::from_registry().await.unwrap();
         sm.send(session_manager::command::Command::AssociateConnection {
             credentials,
             connection: ctx.address().downgrade(),
+            request: id,
         })
         .unwrap();
-        self.send(signaler_protocol::SessionMessage::Authenticated.into_json())
-            .await
+    }
+}
+
+/// the id of `raw_msg` if it is a `Request`
+fn request_id(raw_msg: &str) -> Option<RequestId> {
+    serde_json::from_str::<Request<serde_json::Value>>(raw_msg)
+        .ok()
+        .map(|request| request.id)
+}
+
+/// accepts bare commands as well as commands wrapped in a `Request`
+fn parse_request<C: DeserializeOwned>(raw_msg: &str) -> Result<(Option<RequestId>, C), serde_json::Error> {
+    match serde_json::from_str::<Request<serde_json::Value>>(raw_msg) {
+        Ok(Request { id, command }) => Ok((Some(id), serde_json::from_value(command)?)),
+        Err(_) => Ok((None, serde_json::from_str(raw_msg)?)),
     }
 }
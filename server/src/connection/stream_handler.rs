@@ -19,6 +19,8 @@ impl StreamHandler<WsStreamMessage> for Connection {
                     log::trace!("received {:?}", content);
                     if let Err(error) = self.handle_incoming_message(content, ctx).await {
                         log::error!("connection_id{} {}", self.connection_id, error);
+                        let id = super::request_id(content);
+                        self.send(error.to_session_message().into_reply_json(id)).await;
                     } else {
                         log::trace!("connection_id{} accepted the command", self.connection_id);
                     }
@@ -0,0 +1,27 @@
+use thiserror::Error;
+
+/// The kind of an error.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("unknown user {0:?}")]
+    UnknownUser(String),
+
+    #[error("wrong password")]
+    WrongPassword,
+
+    #[error("username {0:?} is reserved")]
+    UsernameTaken(String),
+
+    #[error("credentials not supported by this store")]
+    Unsupported,
+
+    #[error("no credential store configured")]
+    NoStore,
+
+    #[error("failed to read credentials")]
+    Io(#[from] std::io::Error),
+
+    #[error("failed to parse credentials")]
+    Parsing(#[from] serde_json::Error),
+}
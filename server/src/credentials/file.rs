@@ -0,0 +1,131 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::BufReader,
+    path::Path,
+};
+
+use signaler_protocol::{Credentials, UserProfile};
+use tracing::log;
+
+use super::{CredentialStore, Error, Identity};
+
+/// Reads usernames, passwords and profiles from a json file like `config/test_users.json`
+#[derive(Debug, serde::Deserialize)]
+pub struct FileCredentialStore {
+    credentials: HashMap<String, String>,
+    #[serde(default)]
+    profiles: HashMap<String, UserProfile>,
+    #[serde(default)]
+    admins: HashSet<String>,
+}
+
+impl FileCredentialStore {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        log::debug!("loading credentials from {}", path.display());
+        let store: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        log::trace!("loaded {} credentials", store.credentials.len());
+        Ok(store)
+    }
+
+    fn identity(&self, username: &str) -> Identity {
+        let mut identity = match self.profiles.get(username) {
+            Some(profile) => Identity::new(username, profile.clone()),
+            None => Identity::from_username(username),
+        };
+        identity.is_admin = self.admins.contains(username);
+        identity
+    }
+}
+
+impl CredentialStore for FileCredentialStore {
+    fn authenticate(&self, credentials: &Credentials) -> Result<Identity, Error> {
+        match credentials {
+            Credentials::UsernamePassword { username, password } => match self.credentials.get(username) {
+                Some(expected) if expected == password => Ok(self.identity(username)),
+                Some(_) => Err(Error::WrongPassword),
+                None => Err(Error::UnknownUser(username.clone())),
+            },
+
+            // ad hoc users must not impersonate registered ones
+            Credentials::AdHoc { username } if self.credentials.contains_key(username) => {
+                Err(Error::UsernameTaken(username.clone()))
+            }
+            Credentials::AdHoc { username } => Ok(Identity::from_username(username)),
+
+            Credentials::Token { .. } => Err(Error::Unsupported),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> FileCredentialStore {
+        serde_json::from_value(serde_json::json!({
+            "credentials": { "alice": "secret", "bob": "hunter2" },
+            "profiles": { "alice": { "fullName": "Alice Liddell" } },
+            "admins": ["bob"]
+        }))
+        .unwrap()
+    }
+
+    fn login(username: &str, password: &str) -> Credentials {
+        Credentials::UsernamePassword {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+
+    #[test]
+    fn accepts_password() {
+        let alice = store().authenticate(&login("alice", "secret")).unwrap();
+        assert_eq!(alice.username, "alice");
+        assert_eq!(alice.profile.full_name, "Alice Liddell");
+        assert!(!alice.is_admin);
+
+        let bob = store().authenticate(&login("bob", "hunter2")).unwrap();
+        assert_eq!(bob.profile.full_name, "bob");
+        assert!(bob.is_admin);
+    }
+
+    #[test]
+    fn rejects_wrong_password_and_unknown_user() {
+        assert!(matches!(
+            store().authenticate(&login("alice", "hunter2")),
+            Err(Error::WrongPassword)
+        ));
+        assert!(matches!(
+            store().authenticate(&login("carol", "secret")),
+            Err(Error::UnknownUser(username)) if username == "carol"
+        ));
+    }
+
+    #[test]
+    fn ad_hoc_users_may_not_take_registered_names() {
+        let carol = store()
+            .authenticate(&Credentials::AdHoc {
+                username: "carol".into(),
+            })
+            .unwrap();
+        assert_eq!(carol.username, "carol");
+        assert!(!carol.is_admin);
+
+        assert!(matches!(
+            store().authenticate(&Credentials::AdHoc {
+                username: "alice".into()
+            }),
+            Err(Error::UsernameTaken(username)) if username == "alice"
+        ));
+    }
+
+    #[test]
+    fn tokens_are_not_handled_here() {
+        let token = Credentials::Token {
+            token: uuid::Uuid::new_v4(),
+        };
+        assert!(matches!(store().authenticate(&token), Err(Error::Unsupported)));
+    }
+}
@@ -0,0 +1,42 @@
+//! Verification of [`Credentials`] before a [`Session`](crate::session::Session) is created
+
+use signaler_protocol::{Credentials, UserProfile};
+
+mod error;
+mod file;
+
+pub use error::Error;
+pub use file::FileCredentialStore;
+
+/// The user behind a set of accepted credentials
+#[derive(Clone, Debug)]
+pub struct Identity {
+    pub username: String,
+    pub profile: UserProfile,
+    /// may shut down the server
+    pub is_admin: bool,
+}
+
+impl Identity {
+    pub fn new(username: impl Into<String>, profile: UserProfile) -> Self {
+        Self {
+            username: username.into(),
+            profile,
+            is_admin: false,
+        }
+    }
+
+    /// for users without a stored profile
+    pub fn from_username(username: impl Into<String>) -> Self {
+        let username = username.into();
+        let profile = UserProfile {
+            full_name: username.clone(),
+        };
+        Self::new(username, profile)
+    }
+}
+
+/// Something that can tell valid credentials from invalid ones
+pub trait CredentialStore: Send + Sync {
+    fn authenticate(&self, credentials: &Credentials) -> Result<Identity, Error>;
+}
@@ -0,0 +1,12 @@
+use thiserror::Error;
+
+/// The kind of an error.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("database error")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error("failed to (de)serialize message or room")]
+    Serialization(#[from] serde_json::Error),
+}
@@ -0,0 +1,146 @@
+use std::collections::{HashMap, VecDeque};
+
+use async_trait::async_trait;
+use signaler_protocol::ChatMessage;
+use uuid::Uuid;
+
+use super::{Error, HistoryStore, RoomRecord};
+
+/// Keeps the latest messages for as long as the room is open
+///
+/// every message is numbered in the order it was pushed,
+/// so read markers keep their place when old messages are evicted
+#[derive(Debug)]
+pub struct InMemoryHistory {
+    capacity: usize,
+    messages: VecDeque<(u64, ChatMessage)>,
+    /// number of the next pushed message
+    next: u64,
+    /// number of the latest message each user has read, by username
+    read_markers: HashMap<String, u64>,
+    room: Option<RoomRecord>,
+}
+
+impl InMemoryHistory {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            messages: VecDeque::with_capacity(capacity),
+            next: 0,
+            read_markers: Default::default(),
+            room: None,
+        }
+    }
+
+    fn position(&self, uuid: Uuid) -> Option<usize> {
+        self.messages.iter().position(|(_, message)| message.uuid == uuid)
+    }
+}
+
+impl Default for InMemoryHistory {
+    fn default() -> Self {
+        Self::with_capacity(10_000)
+    }
+}
+
+#[async_trait]
+impl HistoryStore for InMemoryHistory {
+    async fn push(&mut self, message: &ChatMessage) -> Result<(), Error> {
+        if self.messages.len() == self.capacity {
+            self.messages.pop_front();
+        }
+        self.messages.push_back((self.next, message.to_owned()));
+        self.next += 1;
+        Ok(())
+    }
+
+    async fn page(&self, before: Option<Uuid>, limit: usize) -> Result<Vec<ChatMessage>, Error> {
+        let end = match before {
+            Some(uuid) => self.position(uuid).unwrap_or_default(),
+            None => self.messages.len(),
+        };
+        let start = end.saturating_sub(limit);
+        Ok(self
+            .messages
+            .range(start..end)
+            .map(|(_, message)| message.clone())
+            .collect())
+    }
+
+    async fn get(&self, uuid: Uuid) -> Result<Option<ChatMessage>, Error> {
+        Ok(self
+            .messages
+            .iter()
+            .find(|(_, message)| message.uuid == uuid)
+            .map(|(_, message)| message.clone()))
+    }
+
+    async fn thread(&self, root: Uuid, limit: usize) -> Result<Vec<ChatMessage>, Error> {
+        let mut thread = self
+            .messages
+            .iter()
+            .rev()
+            .map(|(_, message)| message)
+            .filter(|message| message.thread == Some(root))
+            .take(limit)
+            .cloned()
+            .collect::<Vec<_>>();
+        thread.extend(self.get(root).await?);
+        thread.reverse();
+        Ok(thread)
+    }
+
+    async fn replace(&mut self, message: &ChatMessage) -> Result<bool, Error> {
+        match self.messages.iter_mut().find(|(_, stored)| stored.uuid == message.uuid) {
+            Some((_, stored)) => {
+                *stored = message.to_owned();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn remove(&mut self, uuid: Uuid) -> Result<bool, Error> {
+        let len = self.messages.len();
+        self.messages.retain(|(_, message)| message.uuid != uuid);
+        Ok(self.messages.len() < len)
+    }
+
+    async fn count_after(&self, after: Option<Uuid>) -> Result<usize, Error> {
+        let read = after
+            .and_then(|uuid| self.position(uuid))
+            .map(|position| position + 1)
+            .unwrap_or_default();
+        Ok(self.messages.len() - read)
+    }
+
+    async fn mark_read(&mut self, username: &str, up_to: Uuid) -> Result<bool, Error> {
+        let number = match self.position(up_to) {
+            Some(position) => self.messages[position].0,
+            None => return Ok(false),
+        };
+        match self.read_markers.get(username) {
+            Some(&marker) if marker >= number => Ok(false),
+            _ => {
+                self.read_markers.insert(username.to_owned(), number);
+                Ok(true)
+            }
+        }
+    }
+
+    async fn unread(&self, username: &str) -> Result<usize, Error> {
+        Ok(match self.read_markers.get(username) {
+            Some(&marker) => self.messages.iter().filter(|(number, _)| *number > marker).count(),
+            None => self.messages.len(),
+        })
+    }
+
+    async fn room(&self) -> Result<Option<RoomRecord>, Error> {
+        Ok(self.room.clone())
+    }
+
+    async fn save_room(&mut self, room: &RoomRecord) -> Result<(), Error> {
+        self.room = Some(room.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+//! Where a [`Room`](crate::room::Room) keeps its [`ChatMessage`]s
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use signaler_protocol::{ChatMessage, RoomSettings};
+use uuid::Uuid;
+
+mod error;
+mod memory;
+mod sqlite;
+#[cfg(test)]
+mod tests;
+
+pub use error::Error;
+pub use memory::InMemoryHistory;
+pub use sqlite::SqliteHistory;
+
+/// Who owns a room and who may join it, saved with its history
+///
+/// so a room that is recreated from a saved history keeps its access rules
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoomRecord {
+    /// username
+    pub owner: String,
+    pub settings: RoomSettings,
+    /// usernames
+    #[serde(default)]
+    pub bans: Vec<String>,
+}
+
+/// The message history of a single room
+#[async_trait]
+pub trait HistoryStore: Send + Sync + std::fmt::Debug {
+    /// appends `message`
+    async fn push(&mut self, message: &ChatMessage) -> Result<(), Error>;
+
+    /// up to `limit` messages older than `before`, oldest first
+    ///
+    /// `before` defaults to the latest message, an unknown `before` yields nothing
+    async fn page(&self, before: Option<Uuid>, limit: usize) -> Result<Vec<ChatMessage>, Error>;
+
+    async fn get(&self, uuid: Uuid) -> Result<Option<ChatMessage>, Error>;
+
+    /// `root` and up to `limit` of the latest replies in its thread, oldest first
+    async fn thread(&self, root: Uuid, limit: usize) -> Result<Vec<ChatMessage>, Error>;
+
+    /// overwrites the stored message with the same uuid, returns `false` if there is none
+    async fn replace(&mut self, message: &ChatMessage) -> Result<bool, Error>;
+
+    /// returns `false` if there was no message with `uuid`
+    async fn remove(&mut self, uuid: Uuid) -> Result<bool, Error>;
+
+    /// how many messages are newer than `after`
+    ///
+    /// counts all messages if `after` is `None` or unknown
+    async fn count_after(&self, after: Option<Uuid>) -> Result<usize, Error>;
+
+    /// moves the read marker of `username` to `up_to`
+    ///
+    /// returns `false` if `up_to` is unknown or not newer than the current marker
+    async fn mark_read(&mut self, username: &str, up_to: Uuid) -> Result<bool, Error>;
+
+    /// how many messages are newer than the read marker of `username`
+    ///
+    /// counts all messages if `username` has not read any,
+    /// the marker keeps counting even after its message is gone
+    async fn unread(&self, username: &str) -> Result<usize, Error>;
+
+    /// the owner and settings saved with this history, if any
+    async fn room(&self) -> Result<Option<RoomRecord>, Error>;
+
+    /// replaces the saved owner and settings
+    async fn save_room(&mut self, room: &RoomRecord) -> Result<(), Error>;
+}
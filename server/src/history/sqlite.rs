@@ -0,0 +1,251 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use signaler_protocol::{ChatMessage, RoomId};
+use tracing::log;
+use uuid::Uuid;
+
+use super::{Error, HistoryStore, RoomRecord};
+
+/// Persists messages in a SQLite database shared by all rooms
+///
+/// a room that is recreated with the same name picks up its old history,
+/// queries run on blocking threads so they don't stall the room
+#[derive(Debug)]
+pub struct SqliteHistory {
+    room: RoomId,
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteHistory {
+    pub async fn open(path: impl Into<PathBuf>, room: RoomId) -> Result<Self, Error> {
+        let path = path.into();
+        log::debug!("opening history of {room:?} in {}", path.display());
+        let connection = async_std::task::spawn_blocking(move || Self::connect(&path)).await?;
+        Ok(Self {
+            room,
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    fn connect(path: &Path) -> Result<Connection, Error> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS messages (
+                id INTEGER PRIMARY KEY,
+                room TEXT NOT NULL,
+                uuid TEXT NOT NULL UNIQUE,
+                message TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS messages_by_room ON messages (room, id);
+            CREATE TABLE IF NOT EXISTS rooms (
+                room TEXT PRIMARY KEY,
+                record TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS read_markers (
+                room TEXT NOT NULL,
+                username TEXT NOT NULL,
+                id INTEGER NOT NULL,
+                PRIMARY KEY (room, username)
+            );",
+        )?;
+        Ok(connection)
+    }
+
+    /// runs `query` with the connection and the name of the room on a blocking thread
+    async fn run<T, F>(&self, query: F) -> Result<T, Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection, &str) -> Result<T, Error> + Send + 'static,
+    {
+        let connection = self.connection.clone();
+        let room = self.room.to_string();
+        async_std::task::spawn_blocking(move || {
+            let connection = connection.lock().unwrap_or_else(PoisonError::into_inner);
+            query(&connection, &room)
+        })
+        .await
+    }
+}
+
+/// the row id of the message with `uuid` in `room`
+fn position(connection: &Connection, room: &str, uuid: Uuid) -> Result<Option<i64>, Error> {
+    Ok(connection
+        .query_row(
+            "SELECT id FROM messages WHERE room = ?1 AND uuid = ?2",
+            params![room, uuid.to_string()],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+fn get(connection: &Connection, room: &str, uuid: Uuid) -> Result<Option<ChatMessage>, Error> {
+    let message = connection
+        .query_row(
+            "SELECT message FROM messages WHERE room = ?1 AND uuid = ?2",
+            params![room, uuid.to_string()],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?;
+    Ok(message.map(|message| serde_json::from_str(&message)).transpose()?)
+}
+
+#[async_trait]
+impl HistoryStore for SqliteHistory {
+    async fn push(&mut self, message: &ChatMessage) -> Result<(), Error> {
+        let uuid = message.uuid.to_string();
+        let message = serde_json::to_string(message)?;
+        self.run(move |connection, room| {
+            connection.execute(
+                "INSERT INTO messages (room, uuid, message) VALUES (?1, ?2, ?3)",
+                params![room, uuid, message],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn page(&self, before: Option<Uuid>, limit: usize) -> Result<Vec<ChatMessage>, Error> {
+        self.run(move |connection, room| {
+            let end = match before {
+                Some(uuid) => match position(connection, room, uuid)? {
+                    Some(end) => end,
+                    None => return Ok(Vec::new()),
+                },
+                None => i64::MAX,
+            };
+            let mut statement = connection
+                .prepare_cached("SELECT message FROM messages WHERE room = ?1 AND id < ?2 ORDER BY id DESC LIMIT ?3")?;
+            let rows = statement.query_map(params![room, end, limit as i64], |row| row.get::<_, String>(0))?;
+            let mut messages = rows
+                .map(|row| Ok(serde_json::from_str(&row?)?))
+                .collect::<Result<Vec<ChatMessage>, Error>>()?;
+            messages.reverse();
+            Ok(messages)
+        })
+        .await
+    }
+
+    async fn get(&self, uuid: Uuid) -> Result<Option<ChatMessage>, Error> {
+        self.run(move |connection, room| get(connection, room, uuid)).await
+    }
+
+    async fn thread(&self, root: Uuid, limit: usize) -> Result<Vec<ChatMessage>, Error> {
+        self.run(move |connection, room| {
+            let mut statement = connection.prepare_cached(
+                "SELECT message FROM messages WHERE room = ?1 AND json_extract(message, '$.thread') = ?2
+                ORDER BY id DESC LIMIT ?3",
+            )?;
+            let rows = statement.query_map(params![room, root.to_string(), limit as i64], |row| {
+                row.get::<_, String>(0)
+            })?;
+            let mut thread = rows
+                .map(|row| Ok(serde_json::from_str(&row?)?))
+                .collect::<Result<Vec<ChatMessage>, Error>>()?;
+            thread.extend(get(connection, room, root)?);
+            thread.reverse();
+            Ok(thread)
+        })
+        .await
+    }
+
+    async fn replace(&mut self, message: &ChatMessage) -> Result<bool, Error> {
+        let uuid = message.uuid.to_string();
+        let message = serde_json::to_string(message)?;
+        self.run(move |connection, room| {
+            let changed = connection.execute(
+                "UPDATE messages SET message = ?3 WHERE room = ?1 AND uuid = ?2",
+                params![room, uuid, message],
+            )?;
+            Ok(changed > 0)
+        })
+        .await
+    }
+
+    async fn remove(&mut self, uuid: Uuid) -> Result<bool, Error> {
+        self.run(move |connection, room| {
+            let changed = connection.execute(
+                "DELETE FROM messages WHERE room = ?1 AND uuid = ?2",
+                params![room, uuid.to_string()],
+            )?;
+            Ok(changed > 0)
+        })
+        .await
+    }
+
+    async fn count_after(&self, after: Option<Uuid>) -> Result<usize, Error> {
+        self.run(move |connection, room| {
+            let start = match after {
+                Some(uuid) => position(connection, room, uuid)?.unwrap_or_default(),
+                None => 0,
+            };
+            let count: i64 = connection.query_row(
+                "SELECT COUNT(*) FROM messages WHERE room = ?1 AND id > ?2",
+                params![room, start],
+                |row| row.get(0),
+            )?;
+            Ok(count as usize)
+        })
+        .await
+    }
+
+    async fn mark_read(&mut self, username: &str, up_to: Uuid) -> Result<bool, Error> {
+        let username = username.to_owned();
+        self.run(move |connection, room| {
+            let id = match position(connection, room, up_to)? {
+                Some(id) => id,
+                None => return Ok(false),
+            };
+            let changed = connection.execute(
+                "INSERT INTO read_markers (room, username, id) VALUES (?1, ?2, ?3)
+                ON CONFLICT (room, username) DO UPDATE SET id = excluded.id WHERE excluded.id > read_markers.id",
+                params![room, username, id],
+            )?;
+            Ok(changed > 0)
+        })
+        .await
+    }
+
+    async fn unread(&self, username: &str) -> Result<usize, Error> {
+        let username = username.to_owned();
+        self.run(move |connection, room| {
+            let count: i64 = connection.query_row(
+                "SELECT COUNT(*) FROM messages WHERE room = ?1 AND id > COALESCE(
+                    (SELECT id FROM read_markers WHERE room = ?1 AND username = ?2), 0
+                )",
+                params![room, username],
+                |row| row.get(0),
+            )?;
+            Ok(count as usize)
+        })
+        .await
+    }
+
+    async fn room(&self) -> Result<Option<RoomRecord>, Error> {
+        self.run(|connection, room| {
+            let record = connection
+                .query_row("SELECT record FROM rooms WHERE room = ?1", params![room], |row| {
+                    row.get::<_, String>(0)
+                })
+                .optional()?;
+            Ok(record.map(|record| serde_json::from_str(&record)).transpose()?)
+        })
+        .await
+    }
+
+    async fn save_room(&mut self, room: &RoomRecord) -> Result<(), Error> {
+        let record = serde_json::to_string(room)?;
+        self.run(move |connection, room| {
+            connection.execute(
+                "INSERT OR REPLACE INTO rooms (room, record) VALUES (?1, ?2)",
+                params![room, record],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
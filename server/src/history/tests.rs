@@ -0,0 +1,219 @@
+//! every backend has to pass the same tests
+
+use async_std::task::block_on;
+use signaler_protocol::{Access, ChatMessage, RoomSettings, SessionId};
+use uuid::Uuid;
+
+use super::{HistoryStore, InMemoryHistory, RoomRecord, SqliteHistory};
+
+fn backends() -> Vec<Box<dyn HistoryStore>> {
+    vec![
+        Box::new(InMemoryHistory::default()),
+        Box::new(block_on(SqliteHistory::open(":memory:", "test".into())).unwrap()),
+    ]
+}
+
+fn message(content: &str) -> ChatMessage {
+    ChatMessage::new(content.into(), SessionId::from(Uuid::new_v4()))
+}
+
+fn reply(content: &str, root: &ChatMessage) -> ChatMessage {
+    ChatMessage {
+        reply_to: Some(root.uuid),
+        thread: Some(root.uuid),
+        ..message(content)
+    }
+}
+
+/// a fresh store of every backend with `count` messages
+fn filled(count: usize) -> Vec<(Box<dyn HistoryStore>, Vec<ChatMessage>)> {
+    backends()
+        .into_iter()
+        .map(|mut history| {
+            let messages = (0..count).map(|i| message(&i.to_string())).collect::<Vec<_>>();
+            for message in &messages {
+                block_on(history.push(message)).unwrap();
+            }
+            (history, messages)
+        })
+        .collect()
+}
+
+fn contents(messages: &[ChatMessage]) -> Vec<&str> {
+    messages.iter().map(|message| message.content.as_str()).collect()
+}
+
+#[test]
+fn page_defaults_to_latest() {
+    for (history, _) in filled(5) {
+        let page = block_on(history.page(None, 2)).unwrap();
+        assert_eq!(contents(&page), ["3", "4"], "{history:?}");
+    }
+}
+
+#[test]
+fn page_before() {
+    for (history, messages) in filled(5) {
+        let page = block_on(history.page(Some(messages[3].uuid), 2)).unwrap();
+        assert_eq!(contents(&page), ["1", "2"], "{history:?}");
+
+        let page = block_on(history.page(Some(messages[1].uuid), 10)).unwrap();
+        assert_eq!(contents(&page), ["0"], "{history:?}");
+    }
+}
+
+#[test]
+fn page_before_unknown_is_empty() {
+    for (history, _) in filled(5) {
+        let page = block_on(history.page(Some(Uuid::new_v4()), 10)).unwrap();
+        assert!(page.is_empty(), "{history:?}");
+    }
+}
+
+#[test]
+fn get_replace_remove() {
+    for (mut history, messages) in filled(3) {
+        let mut edited = messages[1].clone();
+        edited.content = "edited".into();
+        assert!(block_on(history.replace(&edited)).unwrap(), "{history:?}");
+        let stored = block_on(history.get(edited.uuid)).unwrap().unwrap();
+        assert_eq!(stored.content, "edited", "{history:?}");
+
+        assert!(!block_on(history.replace(&message("unknown"))).unwrap(), "{history:?}");
+
+        assert!(block_on(history.remove(edited.uuid)).unwrap(), "{history:?}");
+        assert!(block_on(history.get(edited.uuid)).unwrap().is_none(), "{history:?}");
+        assert!(!block_on(history.remove(edited.uuid)).unwrap(), "{history:?}");
+    }
+}
+
+#[test]
+fn thread_starts_with_root() {
+    for mut history in backends() {
+        let root = message("root");
+        let thread = [
+            root.clone(),
+            reply("first", &root),
+            message("elsewhere"),
+            reply("second", &root),
+            reply("third", &root),
+        ];
+        for message in &thread {
+            block_on(history.push(message)).unwrap();
+        }
+
+        let replies = block_on(history.thread(root.uuid, 2)).unwrap();
+        assert_eq!(contents(&replies), ["root", "second", "third"], "{history:?}");
+
+        let replies = block_on(history.thread(Uuid::new_v4(), 2)).unwrap();
+        assert!(replies.is_empty(), "{history:?}");
+    }
+}
+
+#[test]
+fn count_after() {
+    for (history, messages) in filled(5) {
+        assert_eq!(block_on(history.count_after(None)).unwrap(), 5, "{history:?}");
+        assert_eq!(
+            block_on(history.count_after(Some(messages[2].uuid))).unwrap(),
+            2,
+            "{history:?}"
+        );
+        assert_eq!(
+            block_on(history.count_after(Some(messages[4].uuid))).unwrap(),
+            0,
+            "{history:?}"
+        );
+        assert_eq!(
+            block_on(history.count_after(Some(Uuid::new_v4()))).unwrap(),
+            5,
+            "{history:?}"
+        );
+    }
+}
+
+#[test]
+fn read_marker_only_moves_forward() {
+    for (mut history, messages) in filled(5) {
+        assert_eq!(block_on(history.unread("alice")).unwrap(), 5, "{history:?}");
+
+        assert!(
+            block_on(history.mark_read("alice", messages[2].uuid)).unwrap(),
+            "{history:?}"
+        );
+        assert_eq!(block_on(history.unread("alice")).unwrap(), 2, "{history:?}");
+
+        assert!(
+            !block_on(history.mark_read("alice", messages[1].uuid)).unwrap(),
+            "{history:?}"
+        );
+        assert!(
+            !block_on(history.mark_read("alice", messages[2].uuid)).unwrap(),
+            "{history:?}"
+        );
+        assert!(
+            !block_on(history.mark_read("alice", Uuid::new_v4())).unwrap(),
+            "{history:?}"
+        );
+        assert_eq!(block_on(history.unread("alice")).unwrap(), 2, "{history:?}");
+        assert_eq!(block_on(history.unread("bob")).unwrap(), 5, "{history:?}");
+    }
+}
+
+#[test]
+fn read_marker_outlives_its_message() {
+    for (mut history, messages) in filled(5) {
+        block_on(history.mark_read("alice", messages[2].uuid)).unwrap();
+        block_on(history.remove(messages[2].uuid)).unwrap();
+        assert_eq!(block_on(history.unread("alice")).unwrap(), 2, "{history:?}");
+    }
+}
+
+#[test]
+fn read_marker_survives_eviction() {
+    let mut history = InMemoryHistory::with_capacity(3);
+    let messages = (0..3).map(|i| message(&i.to_string())).collect::<Vec<_>>();
+    for message in &messages {
+        block_on(history.push(message)).unwrap();
+    }
+    block_on(history.mark_read("alice", messages[1].uuid)).unwrap();
+
+    for i in 3..5 {
+        block_on(history.push(&message(&i.to_string()))).unwrap();
+    }
+    assert_eq!(block_on(history.unread("alice")).unwrap(), 3);
+    block_on(history.push(&message("5"))).unwrap();
+    assert_eq!(block_on(history.unread("alice")).unwrap(), 3);
+}
+
+#[test]
+fn room_is_saved_with_history() {
+    for mut history in backends() {
+        assert!(block_on(history.room()).unwrap().is_none(), "{history:?}");
+
+        let record = RoomRecord {
+            owner: "alice".into(),
+            settings: RoomSettings {
+                access: Access::Password {
+                    password: "secret".into(),
+                },
+                ..Default::default()
+            },
+            bans: Vec::new(),
+        };
+        block_on(history.save_room(&record)).unwrap();
+        let record = RoomRecord {
+            bans: vec!["mallory".into()],
+            ..record
+        };
+        block_on(history.save_room(&record)).unwrap();
+
+        let saved = block_on(history.room()).unwrap().unwrap();
+        assert_eq!(saved.owner, "alice", "{history:?}");
+        assert!(
+            matches!(saved.settings.access, Access::Password { ref password } if password == "secret"),
+            "{history:?}"
+        );
+        assert_eq!(saved.bans, ["mallory"], "{history:?}");
+    }
+}
@@ -4,14 +4,20 @@ use tracing::log;
 
 mod config;
 mod connection;
+mod credentials;
+mod history;
 mod metrics;
 mod room;
 mod room_manager;
 mod session;
 mod session_manager;
+mod shutdown;
 mod web_server;
 
 use crate::config::Config;
+use crate::credentials::FileCredentialStore;
+use crate::room_manager::RoomManager;
+use crate::session_manager::SessionManager;
 use crate::web_server::WebServer;
 
 #[async_std::main]
@@ -32,12 +38,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     log::debug!("log config {:?}", config.log_config);
 
-    WebServer::from_registry()
+    let credential_store = FileCredentialStore::from_path(&config.credentials.file)?;
+    SessionManager::from_registry()
         .await?
+        .send(session_manager::command::Command::UseCredentialStore(Box::new(
+            credential_store,
+        )))?;
+
+    let room_manager = RoomManager::from_registry().await?;
+    room_manager.send(room_manager::Command::SetIdleTimeout(config.rooms.idle_timeout()))?;
+    room_manager.send(room_manager::Command::SetMaxParticipants(config.rooms.max_participants))?;
+    if let Some(database) = config.history.database {
+        room_manager.send(room_manager::Command::UseHistoryDatabase(database))?;
+    }
+
+    let web_server = WebServer::from_registry().await?;
+    web_server
         .call(web_server::Listen {
             socket: ([0, 0, 0, 0], config.server.port).into(),
         })
-        .await?;
+        .await??;
+
+    async_std::task::spawn(async {
+        match shutdown::signal().await {
+            Ok(()) => shutdown::shut_down("received signal".into()).await,
+            Err(error) => log::error!("unable to listen for signals {}", error),
+        }
+    });
+
+    web_server.wait_for_stop().await;
 
     Ok(())
 }
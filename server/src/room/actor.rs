@@ -1,12 +1,16 @@
+use std::time::Duration;
+
 use async_trait::async_trait;
 use hannibal::{Actor, Handler};
-use protocol::ChatMessage;
+use protocol::{ChatMessage, ErrorCode};
 use signaler_protocol as protocol;
 use tracing::log;
 use uuid::Uuid;
 
+use crate::session::command::PresenceChanged;
+
 use super::{
-    command::{ChatRoomCommand, Command},
+    command::{ChatRoomCommand, Close, CloseIfIdle, Command, Gc, GetInfo, GetUnread, RoomToSession, Signal},
     Room,
 };
 
@@ -14,6 +18,7 @@ use super::{
 impl Actor for Room {
     async fn started(&mut self, ctx: &mut hannibal::Context<Self>) -> hannibal::Result<()> {
         log::info!("starting Room {:?}", ctx.actor_id());
+        ctx.send_interval(Gc, Duration::from_secs(5));
         Ok(())
     }
     async fn stopped(&mut self, _ctx: &mut hannibal::Context<Self>) {
@@ -26,33 +31,164 @@ impl Handler<Command> for Room {
     async fn handle(&mut self, ctx: &mut hannibal::Context<Self>, cmd: Command) {
         log::trace!("received command {:?}", cmd);
         match cmd {
-            Command::AddParticipant { participant } => self.add_participant(participant, ctx),
+            Command::AddParticipant {
+                participant,
+                password,
+                request,
+            } => self.add_participant(participant, password, request, ctx).await,
         }
     }
 }
 
+#[async_trait]
+impl Handler<GetInfo> for Room {
+    async fn handle(&mut self, _ctx: &mut hannibal::Context<Self>, _: GetInfo) -> Option<protocol::RoomInfo> {
+        self.is_listed().then(|| self.info())
+    }
+}
+
+#[async_trait]
+impl Handler<GetUnread> for Room {
+    async fn handle(&mut self, _ctx: &mut hannibal::Context<Self>, msg: GetUnread) -> usize {
+        self.unread(&msg.username).await
+    }
+}
+
+#[async_trait]
+impl Handler<Close> for Room {
+    async fn handle(&mut self, ctx: &mut hannibal::Context<Self>, msg: Close) -> bool {
+        if msg.username != self.owner && !msg.is_admin {
+            log::warn!("{} is not allowed to close {:?}", msg.session_id, self.id);
+            return false;
+        }
+        // the closing session gets a direct reply instead
+        self.roster.remove(&msg.session_id);
+        self.close(ctx);
+        true
+    }
+}
+
 #[async_trait]
 impl Handler<ChatRoomCommand> for Room {
     async fn handle(&mut self, ctx: &mut hannibal::Context<Self>, cmd: ChatRoomCommand) {
         log::trace!("received command {:?}", cmd);
+        if matches!(cmd.command, protocol::ChatRoomCommand::Leave) && self.leave_waitlist(cmd.session_id, cmd.request) {
+            return;
+        }
+        if !self.is_participant(&cmd.session_id) {
+            log::warn!("rejecting command from {}, not a participant", cmd.session_id);
+            if let Some(session) = cmd.session.upgrade() {
+                if let Err(error) = session.send(RoomToSession::Error {
+                    code: ErrorCode::NotAMember,
+                    message: format!("not a participant of {}", self.id),
+                    request: cmd.request,
+                }) {
+                    log::warn!("failed to send Error {error}");
+                }
+            }
+            return;
+        }
         match cmd.command {
-            protocol::ChatRoomCommand::Leave => {
-                log::trace!("received leave, but from whom?");
-                todo!()
-            }
-            protocol::ChatRoomCommand::Message { content } => {
-                log::trace!("forwarding message {content:?}");
-                self.forward_to_participants(
-                    ChatMessage {
-                        content,
-                        sender: cmd.session_id.into(),
-                        sent: chrono::Utc::now(),
-                        uuid: Uuid::new_v4(),
-                    },
-                    ctx,
-                )
-            }
-            protocol::ChatRoomCommand::ListParticipants => todo!(),
+            protocol::ChatRoomCommand::Leave => self.remove_participant(cmd.session_id, cmd.request, ctx).await,
+            protocol::ChatRoomCommand::Message { .. } | protocol::ChatRoomCommand::Edit { .. }
+                if self.is_muted(&cmd.session_id) =>
+            {
+                self.send_error(cmd.session_id, cmd.request, ErrorCode::Forbidden, "muted in this room")
+            }
+            protocol::ChatRoomCommand::Message { content, reply_to } => match self.thread_of(reply_to).await {
+                Ok(thread) => {
+                    log::trace!("forwarding message {content:?}");
+                    self.forward_to_participants(
+                        ChatMessage {
+                            content,
+                            sender: cmd.session_id.into(),
+                            sent: chrono::Utc::now(),
+                            uuid: Uuid::new_v4(),
+                            edited: None,
+                            reactions: Vec::new(),
+                            reply_to,
+                            thread,
+                        },
+                        ctx,
+                    )
+                    .await
+                }
+                Err(code) => self.send_error(cmd.session_id, cmd.request, code, "can't reply"),
+            },
+            protocol::ChatRoomCommand::ListParticipants => self.send_roster(cmd.session_id, cmd.request),
+            protocol::ChatRoomCommand::History { before, limit } => {
+                self.send_history(cmd.session_id, cmd.request, before, limit).await
+            }
+            protocol::ChatRoomCommand::Thread { root } => self.send_thread(cmd.session_id, cmd.request, root).await,
+            protocol::ChatRoomCommand::Ephemeral { signal, active } => {
+                self.set_ephemeral(cmd.session_id, signal, active)
+            }
+            protocol::ChatRoomCommand::Offer { to, sdp } => {
+                self.relay_signal(cmd.session_id, to.into(), cmd.request, Signal::Offer { sdp })
+            }
+            protocol::ChatRoomCommand::Answer { to, sdp } => {
+                self.relay_signal(cmd.session_id, to.into(), cmd.request, Signal::Answer { sdp })
+            }
+            protocol::ChatRoomCommand::IceCandidate { to, candidate } => self.relay_signal(
+                cmd.session_id,
+                to.into(),
+                cmd.request,
+                Signal::IceCandidate { candidate },
+            ),
+            protocol::ChatRoomCommand::Hangup { to } => {
+                self.relay_signal(cmd.session_id, to.into(), cmd.request, Signal::Hangup)
+            }
+            protocol::ChatRoomCommand::Kick { participant } => {
+                self.kick(cmd.session_id, participant.into(), cmd.request, ctx).await
+            }
+            protocol::ChatRoomCommand::Ban { participant } => {
+                self.ban(cmd.session_id, participant.into(), cmd.request, ctx).await
+            }
+            protocol::ChatRoomCommand::Mute { participant } => {
+                self.set_muted(cmd.session_id, participant.into(), true, cmd.request)
+            }
+            protocol::ChatRoomCommand::Unmute { participant } => {
+                self.set_muted(cmd.session_id, participant.into(), false, cmd.request)
+            }
+            protocol::ChatRoomCommand::SetRole { participant, role } => {
+                self.set_role(cmd.session_id, participant.into(), role, cmd.request)
+            }
+            protocol::ChatRoomCommand::Invite { username } => self.invite(cmd.session_id, username, cmd.request).await,
+            protocol::ChatRoomCommand::Edit { uuid, content } => {
+                self.edit_message(cmd.session_id, uuid, content, cmd.request).await
+            }
+            protocol::ChatRoomCommand::Delete { uuid } => self.delete_message(cmd.session_id, uuid, cmd.request).await,
+            protocol::ChatRoomCommand::React { uuid, emoji } => {
+                self.set_reaction(cmd.session_id, uuid, emoji, true, cmd.request).await
+            }
+            protocol::ChatRoomCommand::Unreact { uuid, emoji } => {
+                self.set_reaction(cmd.session_id, uuid, emoji, false, cmd.request).await
+            }
+            protocol::ChatRoomCommand::MarkRead { up_to, receipt } => {
+                self.mark_read(cmd.session_id, up_to, receipt, cmd.request).await
+            }
         }
     }
 }
+
+#[async_trait]
+impl Handler<PresenceChanged> for Room {
+    async fn handle(&mut self, _ctx: &mut hannibal::Context<Self>, msg: PresenceChanged) {
+        self.broadcast_presence(msg);
+    }
+}
+
+#[async_trait]
+impl Handler<Gc> for Room {
+    async fn handle(&mut self, ctx: &mut hannibal::Context<Self>, _: Gc) {
+        self.gc(ctx).await;
+        self.expire_ephemeral();
+    }
+}
+
+#[async_trait]
+impl Handler<CloseIfIdle> for Room {
+    async fn handle(&mut self, ctx: &mut hannibal::Context<Self>, _: CloseIfIdle) -> bool {
+        self.close_if_idle(ctx)
+    }
+}
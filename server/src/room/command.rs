@@ -1,17 +1,53 @@
 use hannibal::WeakAddr;
-use signaler_protocol::{self as protocol, ChatMessage, RoomId};
+use signaler_protocol::{
+    self as protocol, ChatMessage, Ephemeral, ErrorCode, IceCandidate, Participant, Reaction, RequestId, RoomEvent,
+    RoomId, RoomInfo,
+};
 
-use crate::session::SessionId;
+use uuid::Uuid;
+
+use crate::session::{Session, SessionId};
 
 use super::{participant::RoomParticipant, Room};
 
 #[derive(Debug)]
 #[hannibal::message]
 pub enum Command {
-    AddParticipant { participant: RoomParticipant },
-    // GetParticipants {
-    //     session_id: SessionId,
-    // },
+    AddParticipant {
+        participant: RoomParticipant,
+        password: Option<String>,
+        request: Option<RequestId>,
+    },
+}
+
+/// results in `None` if the room is not listed
+#[derive(Debug)]
+#[hannibal::message(result = "Option<RoomInfo>")]
+pub struct GetInfo;
+
+/// how many messages `username` has not read yet
+#[derive(Debug)]
+#[hannibal::message(result = "usize")]
+pub struct GetUnread {
+    pub username: String,
+}
+
+/// stops the room if it has been empty for too long
+///
+/// results in `true` if it did, only the `RoomManager` sends this so no join can slip in before the stop
+#[derive(Debug)]
+#[hannibal::message(result = "bool")]
+pub struct CloseIfIdle;
+
+/// closes the room if the session is allowed to
+///
+/// results in `false` if it is not
+#[derive(Debug)]
+#[hannibal::message(result = "bool")]
+pub struct Close {
+    pub session_id: SessionId,
+    pub username: String,
+    pub is_admin: bool,
 }
 
 #[derive(Debug)]
@@ -19,6 +55,31 @@ pub enum Command {
 pub struct ChatRoomCommand {
     pub command: protocol::ChatRoomCommand,
     pub session_id: SessionId,
+    /// where to reply if the session is not a participant (anymore)
+    pub session: WeakAddr<Session>,
+    /// id of the request this command came in, echoed on direct replies
+    pub request: Option<protocol::RequestId>,
+}
+
+/// WebRTC signaling, relayed from one participant to another
+#[derive(Debug)]
+pub enum Signal {
+    Offer { sdp: String },
+    Answer { sdp: String },
+    IceCandidate { candidate: IceCandidate },
+    Hangup,
+}
+
+impl Signal {
+    pub fn into_session_message(self, room: RoomId, from: SessionId) -> protocol::SessionMessage {
+        let from = from.into();
+        match self {
+            Signal::Offer { sdp } => protocol::SessionMessage::Offer { room, from, sdp },
+            Signal::Answer { sdp } => protocol::SessionMessage::Answer { room, from, sdp },
+            Signal::IceCandidate { candidate } => protocol::SessionMessage::IceCandidate { room, from, candidate },
+            Signal::Hangup => protocol::SessionMessage::Hangup { room, from },
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -26,14 +87,107 @@ pub struct ChatRoomCommand {
 pub enum RoomToSession {
     Joined(RoomId, WeakAddr<Room>),
 
-    ChatMessage { room: RoomId, message: ChatMessage },
-    // History { room: RoomId, messages: Vec<ChatMessage> },
+    ChatMessage {
+        room: RoomId,
+        message: ChatMessage,
+    },
+
+    MessageEdited {
+        room: RoomId,
+        message: ChatMessage,
+    },
+
+    MessageDeleted {
+        room: RoomId,
+        uuid: Uuid,
+    },
+
+    Reactions {
+        room: RoomId,
+        uuid: Uuid,
+        reactions: Vec<Reaction>,
+    },
+
+    Ephemeral {
+        room: RoomId,
+        from: SessionId,
+        signal: Ephemeral,
+        active: bool,
+    },
+
+    /// `reader` has read a message of this session
+    ReadReceipt {
+        room: RoomId,
+        uuid: Uuid,
+        reader: SessionId,
+    },
+
+    History {
+        room: RoomId,
+        messages: Vec<ChatMessage>,
+        request: Option<RequestId>,
+    },
+
+    Thread {
+        room: RoomId,
+        root: Uuid,
+        messages: Vec<ChatMessage>,
+        request: Option<RequestId>,
+    },
 
-    // RoomState { room: RoomId, roster: Vec<Participant> },
+    RoomState {
+        room: RoomId,
+        roster: Vec<Participant>,
+        request: Option<RequestId>,
+    },
 
-    // RoomEvent { room: RoomId, event: RoomEvent },
+    RoomEvent {
+        room: RoomId,
+        event: RoomEvent,
+    },
 
-    // JoinDeclined { room: RoomId },
+    Signal {
+        room: RoomId,
+        from: SessionId,
+        signal: Signal,
+    },
 
-    // Left { room: RoomId },
+    /// a command of this session could not be carried out
+    Error {
+        code: ErrorCode,
+        message: String,
+        request: Option<RequestId>,
+    },
+
+    /// the room has been closed and the session is no longer a member
+    Closed {
+        room: RoomId,
+    },
+
+    /// a moderator removed the session from the room
+    Kicked {
+        room: RoomId,
+    },
+
+    JoinDeclined {
+        room: RoomId,
+        reason: String,
+        request: Option<RequestId>,
+    },
+
+    Waitlisted {
+        room: RoomId,
+        addr: WeakAddr<Room>,
+        position: usize,
+        request: Option<RequestId>,
+    },
+
+    Left {
+        room: RoomId,
+        request: Option<RequestId>,
+    },
 }
+
+#[hannibal::message]
+#[derive(Clone, Copy, Debug)]
+pub struct Gc;
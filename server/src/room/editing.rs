@@ -0,0 +1,261 @@
+use signaler_protocol::{ChatMessage, ErrorCode, Reaction, RequestId};
+use tracing::log;
+use uuid::Uuid;
+
+use crate::session::SessionId;
+
+use super::{command::RoomToSession, Room, MAX_HISTORY_PAGE};
+
+/// replies, edits and reactions to messages that have already been sent
+impl Room {
+    /// the thread a reply to `reply_to` belongs to, the parent has to exist
+    pub async fn thread_of(&self, reply_to: Option<Uuid>) -> Result<Option<Uuid>, ErrorCode> {
+        match reply_to {
+            Some(parent) => {
+                let parent = self.stored_message(parent).await?;
+                Ok(Some(parent.thread.unwrap_or(parent.uuid)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub async fn send_thread(&self, session_id: SessionId, request: Option<RequestId>, root: Uuid) {
+        let messages = match self.history.thread(root, MAX_HISTORY_PAGE).await {
+            Ok(messages) if !messages.is_empty() => messages,
+            Ok(_) => {
+                self.send_error(
+                    session_id,
+                    request,
+                    ErrorCode::MessageNotFound,
+                    format!("no thread {root}"),
+                );
+                return;
+            }
+            Err(error) => {
+                log::error!("failed to read thread from {:?} {error}", self.id);
+                self.send_error(
+                    session_id,
+                    request,
+                    ErrorCode::Internal,
+                    format!("can't read thread {root}"),
+                );
+                return;
+            }
+        };
+        self.send_to(
+            &session_id,
+            RoomToSession::Thread {
+                room: self.id.clone(),
+                root,
+                messages,
+                request,
+            },
+        );
+    }
+
+    pub async fn stored_message(&self, uuid: Uuid) -> Result<ChatMessage, ErrorCode> {
+        self.history
+            .get(uuid)
+            .await
+            .map_err(|error| {
+                log::error!("failed to read history of {:?} {error}", self.id);
+                ErrorCode::Internal
+            })?
+            .ok_or(ErrorCode::MessageNotFound)
+    }
+
+    /// the stored message with `uuid`, if `session_id` sent it
+    async fn own_message(&self, session_id: SessionId, uuid: Uuid) -> Result<ChatMessage, ErrorCode> {
+        let message = self.stored_message(uuid).await?;
+        if message.sender == session_id.into() {
+            Ok(message)
+        } else {
+            Err(ErrorCode::Forbidden)
+        }
+    }
+
+    pub async fn edit_message(
+        &mut self,
+        session_id: SessionId,
+        uuid: Uuid,
+        content: String,
+        request: Option<RequestId>,
+    ) {
+        let mut message = match self.own_message(session_id, uuid).await {
+            Ok(message) => message,
+            Err(code) => {
+                self.send_error(session_id, request, code, format!("can't edit {uuid}"));
+                return;
+            }
+        };
+        message.content = content;
+        message.edited = Some(chrono::Utc::now());
+        match self.history.replace(&message).await {
+            Ok(true) => self.broadcast(|room| RoomToSession::MessageEdited {
+                room,
+                message: message.clone(),
+            }),
+            Ok(false) => self.send_error(
+                session_id,
+                request,
+                ErrorCode::MessageNotFound,
+                format!("can't edit {uuid}"),
+            ),
+            Err(error) => {
+                log::error!("failed to edit message in {:?} {error}", self.id);
+                self.send_error(session_id, request, ErrorCode::Internal, format!("can't edit {uuid}"));
+            }
+        }
+    }
+
+    pub async fn delete_message(&mut self, session_id: SessionId, uuid: Uuid, request: Option<RequestId>) {
+        if let Err(code) = self.own_message(session_id, uuid).await {
+            self.send_error(session_id, request, code, format!("can't delete {uuid}"));
+            return;
+        }
+        match self.history.remove(uuid).await {
+            Ok(true) => self.broadcast(|room| RoomToSession::MessageDeleted { room, uuid }),
+            Ok(false) => self.send_error(
+                session_id,
+                request,
+                ErrorCode::MessageNotFound,
+                format!("can't delete {uuid}"),
+            ),
+            Err(error) => {
+                log::error!("failed to delete message in {:?} {error}", self.id);
+                self.send_error(session_id, request, ErrorCode::Internal, format!("can't delete {uuid}"));
+            }
+        }
+    }
+
+    /// adds or removes the reaction of `session_id` and tells everybody
+    pub async fn set_reaction(
+        &mut self,
+        session_id: SessionId,
+        uuid: Uuid,
+        emoji: String,
+        reacted: bool,
+        request: Option<RequestId>,
+    ) {
+        if emoji.is_empty() || emoji.chars().count() > MAX_EMOJI_LENGTH {
+            self.send_error(session_id, request, ErrorCode::InvalidCommand, "not an emoji");
+            return;
+        }
+        let mut message = match self.stored_message(uuid).await {
+            Ok(message) => message,
+            Err(code) => {
+                self.send_error(session_id, request, code, format!("can't react to {uuid}"));
+                return;
+            }
+        };
+        if !update_reactions(&mut message.reactions, emoji, session_id, reacted) {
+            return;
+        }
+        match self.history.replace(&message).await {
+            Ok(_) => self.broadcast(|room| RoomToSession::Reactions {
+                room,
+                uuid,
+                reactions: message.reactions.clone(),
+            }),
+            Err(error) => {
+                log::error!("failed to store reaction in {:?} {error}", self.id);
+                self.send_error(
+                    session_id,
+                    request,
+                    ErrorCode::Internal,
+                    format!("can't react to {uuid}"),
+                );
+            }
+        }
+    }
+}
+
+/// longest accepted emoji in chars, some are made of several code points
+const MAX_EMOJI_LENGTH: usize = 16;
+
+/// returns `false` if nothing changed
+fn update_reactions(reactions: &mut Vec<Reaction>, emoji: String, session_id: SessionId, reacted: bool) -> bool {
+    let reactor = session_id.into();
+    let position = reactions.iter().position(|reaction| reaction.emoji == emoji);
+    match (position, reacted) {
+        (Some(position), true) => {
+            let reactors = &mut reactions[position].reactors;
+            if reactors.contains(&reactor) {
+                return false;
+            }
+            reactors.push(reactor);
+        }
+        (None, true) => reactions.push(Reaction {
+            emoji,
+            reactors: vec![reactor],
+        }),
+        (Some(position), false) => {
+            let reactors = &mut reactions[position].reactors;
+            let len = reactors.len();
+            reactors.retain(|r| *r != reactor);
+            if reactors.len() == len {
+                return false;
+            }
+            if reactors.is_empty() {
+                reactions.remove(position);
+            }
+        }
+        (None, false) => return false,
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emojis(reactions: &[Reaction]) -> Vec<(&str, usize)> {
+        reactions
+            .iter()
+            .map(|reaction| (reaction.emoji.as_str(), reaction.reactors.len()))
+            .collect()
+    }
+
+    #[test]
+    fn reactions_are_grouped_by_emoji() {
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut reactions = Vec::new();
+        assert!(update_reactions(&mut reactions, "👍".into(), alice, true));
+        assert!(update_reactions(&mut reactions, "👍".into(), bob, true));
+        assert!(update_reactions(&mut reactions, "🎉".into(), bob, true));
+        assert_eq!(emojis(&reactions), [("👍", 2), ("🎉", 1)]);
+    }
+
+    #[test]
+    fn reacting_twice_changes_nothing() {
+        let alice = Uuid::new_v4();
+        let mut reactions = Vec::new();
+        assert!(update_reactions(&mut reactions, "👍".into(), alice, true));
+        assert!(!update_reactions(&mut reactions, "👍".into(), alice, true));
+        assert_eq!(emojis(&reactions), [("👍", 1)]);
+    }
+
+    #[test]
+    fn last_reactor_removes_the_emoji() {
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut reactions = Vec::new();
+        update_reactions(&mut reactions, "👍".into(), alice, true);
+        update_reactions(&mut reactions, "👍".into(), bob, true);
+
+        assert!(update_reactions(&mut reactions, "👍".into(), alice, false));
+        assert_eq!(emojis(&reactions), [("👍", 1)]);
+        assert!(update_reactions(&mut reactions, "👍".into(), bob, false));
+        assert!(reactions.is_empty());
+    }
+
+    #[test]
+    fn removing_unknown_reactions_changes_nothing() {
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut reactions = Vec::new();
+        assert!(!update_reactions(&mut reactions, "👍".into(), alice, false));
+        update_reactions(&mut reactions, "👍".into(), alice, true);
+        assert!(!update_reactions(&mut reactions, "👍".into(), bob, false));
+        assert!(!update_reactions(&mut reactions, "🎉".into(), alice, false));
+        assert_eq!(emojis(&reactions), [("👍", 1)]);
+    }
+}
@@ -0,0 +1,82 @@
+use std::{
+    mem,
+    time::{Duration, Instant},
+};
+
+use signaler_protocol::Ephemeral;
+use tracing::log;
+
+use crate::session::{command::PresenceChanged, SessionId};
+
+use super::{command::RoomToSession, Room};
+
+/// how long an ephemeral signal stays active without being refreshed
+const EPHEMERAL_TTL: Duration = Duration::from_secs(10);
+
+/// ephemeral signals and presence
+impl Room {
+    /// tells everybody else, nothing is stored in history
+    pub fn set_ephemeral(&mut self, session_id: SessionId, signal: Ephemeral, active: bool) {
+        let key = (session_id, mem::discriminant(&signal));
+        if active {
+            self.ephemeral.insert(key, (signal.clone(), Instant::now()));
+        } else if self.ephemeral.remove(&key).is_none() {
+            return;
+        }
+        self.broadcast_ephemeral(session_id, signal, active);
+    }
+
+    /// ends signals that were not refreshed in time or whose sender has left
+    pub fn expire_ephemeral(&mut self) {
+        let expired = self
+            .ephemeral
+            .iter()
+            .filter(|((session_id, _), (_, refreshed))| {
+                refreshed.elapsed() > EPHEMERAL_TTL || !self.roster.contains_key(session_id)
+            })
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+
+        for key in expired {
+            if let Some((signal, _)) = self.ephemeral.remove(&key) {
+                log::trace!("{signal:?} of {} expired", key.0);
+                self.broadcast_ephemeral(key.0, signal, false);
+            }
+        }
+    }
+
+    /// passes a presence change on to the other participants
+    pub fn broadcast_presence(&self, update: PresenceChanged) {
+        if !self.is_participant(&update.session_id) {
+            return;
+        }
+        let others = self
+            .roster
+            .values()
+            .filter(|participant| participant.session_id != update.session_id)
+            .filter_map(|participant| participant.addr.upgrade());
+        for participant in others {
+            if let Err(error) = participant.send(update.clone()) {
+                log::warn!("failed to send PresenceChanged {error}");
+            }
+        }
+    }
+
+    fn broadcast_ephemeral(&self, from: SessionId, signal: Ephemeral, active: bool) {
+        let others = self
+            .roster
+            .values()
+            .filter(|participant| participant.session_id != from)
+            .filter_map(|participant| participant.addr.upgrade());
+        for participant in others {
+            if let Err(error) = participant.send(RoomToSession::Ephemeral {
+                room: self.id.clone(),
+                from,
+                signal: signal.clone(),
+                active,
+            }) {
+                log::warn!("failed to send Ephemeral {error}");
+            }
+        }
+    }
+}
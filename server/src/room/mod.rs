@@ -1,55 +1,414 @@
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    mem::Discriminant,
+    time::{Duration, Instant},
+};
 
-use signaler_protocol::ChatMessage;
 pub use signaler_protocol::RoomId;
+use signaler_protocol::{Access, ChatMessage, Ephemeral, ErrorCode, Participant, RequestId, Role, RoomEvent, RoomInfo};
 
 use hannibal::Context;
 use tracing::log;
+use uuid::Uuid;
 
 mod actor;
 pub mod command;
+mod editing;
+mod ephemeral;
+mod moderation;
+mod receipts;
 pub use command::Command;
 
-use crate::{room::command::RoomToSession, session::SessionId};
+use crate::{
+    history::{HistoryStore, RoomRecord},
+    room::command::{RoomToSession, Signal},
+    session::SessionId,
+};
 
 use self::participant::RoomParticipant;
 
 pub mod participant;
 
+/// how many messages a participant receives when joining
+const HISTORY_ON_JOIN: usize = 50;
+
+/// upper bound for `ChatRoomCommand::History { limit }`
+const MAX_HISTORY_PAGE: usize = 500;
+
+/// a session waiting for a free spot in a full room
+#[derive(Debug)]
+struct Waiting {
+    participant: RoomParticipant,
+    password: Option<String>,
+    request: Option<RequestId>,
+}
+
 #[derive(Debug)]
 pub struct Room {
     id: RoomId,
-    capacity: usize,
-    history: VecDeque<ChatMessage>,
+    /// username of whoever created the room
+    owner: String,
+    history: Box<dyn HistoryStore>,
     roster: HashMap<SessionId, RoomParticipant>,
+    /// who may join
+    access: Access,
+    /// usernames that may not join
+    bans: HashSet<String>,
+    /// usernames that may not send messages
+    mutes: HashSet<String>,
+    /// roles other than `Member`, by username
+    roles: HashMap<String, Role>,
+    max_participants: Option<usize>,
+    /// only rooms with a waitlist queue joins while full
+    waitlist: Option<VecDeque<Waiting>>,
+
+    /// signals that are broadcast but never stored, and when they were last refreshed
+    ephemeral: HashMap<(SessionId, Discriminant<Ephemeral>), (Ephemeral, Instant)>,
+
+    /// the room stops after being empty for this long
+    idle_timeout: Option<Duration>,
+    /// when the last participant left
+    empty_since: Option<Instant>,
 }
 
 impl Room {
-    pub fn new(id: RoomId) -> Self {
-        let capacity: usize = 10_000;
+    pub fn new(id: RoomId, record: RoomRecord, idle_timeout: Option<Duration>, history: Box<dyn HistoryStore>) -> Self {
+        let RoomRecord { owner, settings, bans } = record;
         Self {
             id,
-            capacity,
-            history: VecDeque::with_capacity(capacity),
+            owner,
+            history,
             roster: Default::default(),
+            access: settings.access,
+            bans: bans.into_iter().collect(),
+            mutes: Default::default(),
+            roles: Default::default(),
+            max_participants: settings.max_participants,
+            waitlist: settings.waitlist.then(VecDeque::new),
+            ephemeral: Default::default(),
+            idle_timeout,
+            empty_since: Some(Instant::now()),
+        }
+    }
+
+    pub fn info(&self) -> RoomInfo {
+        RoomInfo {
+            room: self.id.clone(),
+            participants: self.roster.len(),
+        }
+    }
+
+    pub fn is_participant(&self, session_id: &SessionId) -> bool {
+        self.roster.contains_key(session_id)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.max_participants
+            .map(|max| self.roster.len() >= max)
+            .unwrap_or_default()
+    }
+
+    /// private rooms are not listed
+    pub fn is_listed(&self) -> bool {
+        !matches!(self.access, Access::Private)
+    }
+
+    /// why `participant` may not join, if it may not
+    fn check_access(&self, participant: &RoomParticipant, password: Option<&str>) -> Result<(), String> {
+        if self.bans.contains(&participant.username) {
+            return Err(format!("banned from {}", self.id));
+        }
+        if participant.username == self.owner || self.is_participant(&participant.session_id) {
+            return Ok(());
+        }
+        match &self.access {
+            Access::Public | Access::Private => Ok(()),
+            Access::Password { password: expected } if password == Some(expected.as_str()) => Ok(()),
+            Access::Password { .. } => Err("wrong password".into()),
+            Access::InviteOnly { invited } if invited.contains(&participant.username) => Ok(()),
+            Access::InviteOnly { .. } => Err(format!("not invited to {}", self.id)),
         }
     }
 
-    pub fn add_participant(&mut self, participant: RoomParticipant, ctx: &mut Context<Self>) {
-        if let Some(ref participant_addr) = participant.addr.upgrade() {
+    pub async fn add_participant(
+        &mut self,
+        mut participant: RoomParticipant,
+        password: Option<String>,
+        request: Option<RequestId>,
+        ctx: &mut Context<Self>,
+    ) {
+        if let Some(participant_addr) = participant.addr.upgrade() {
+            if let Err(reason) = self.check_access(&participant, password.as_deref()) {
+                log::debug!("declining {} to join {:?}: {reason}", participant.username, self.id);
+                if let Err(error) = participant_addr.send(RoomToSession::JoinDeclined {
+                    room: self.id.clone(),
+                    reason,
+                    request,
+                }) {
+                    log::warn!("failed to send JoinDeclined {error}");
+                }
+                return;
+            }
+            if self.is_full() && !self.is_participant(&participant.session_id) {
+                log::debug!("{:?} is full, {} has to wait", self.id, participant.username);
+                let reply = self.enqueue(
+                    Waiting {
+                        participant,
+                        password,
+                        request,
+                    },
+                    ctx,
+                );
+                if let Err(error) = participant_addr.send(reply) {
+                    log::warn!("failed to send {error}");
+                }
+                return;
+            }
+            log::debug!("{} joins {:?}", participant.profile.full_name, self.id);
+            participant.role = self.role_of(&participant.username);
+            participant.muted = self.mutes.contains(&participant.username);
+            if !self.is_participant(&participant.session_id) {
+                self.broadcast_event(RoomEvent::ParticipantJoined {
+                    name: participant.profile.full_name.clone(),
+                });
+            }
             if let Some(old) = self.roster.insert(participant.session_id, participant) {
                 log::warn!("replacing existing an participant {:?}", old)
             }
+            self.empty_since = None;
             if let Err(error) = participant_addr.send(RoomToSession::Joined(self.id.clone(), ctx.address().downgrade()))
             {
                 log::warn!("failed to send Joined {error}");
             }
+            let messages = self.history_page(None, HISTORY_ON_JOIN).await;
+            if let Err(error) = participant_addr.send(RoomToSession::History {
+                room: self.id.clone(),
+                messages,
+                request,
+            }) {
+                log::warn!("failed to send History {error}");
+            }
         }
         log::debug!("room {:?} has {} participants", self.id, self.roster.len())
     }
 
-    pub fn forward_to_participants(&mut self, message: ChatMessage, _ctx: &mut Context<Self>) {
-        self.store_message(&message);
+    /// puts `waiting` on the waitlist, if there is one
+    fn enqueue(&mut self, waiting: Waiting, ctx: &mut Context<Self>) -> RoomToSession {
+        let room = self.id.clone();
+        let request = waiting.request;
+        match self.waitlist.as_mut() {
+            Some(waitlist) => {
+                waitlist.retain(|w| w.participant.session_id != waiting.participant.session_id);
+                waitlist.push_back(waiting);
+                RoomToSession::Waitlisted {
+                    room,
+                    addr: ctx.address().downgrade(),
+                    position: waitlist.len(),
+                    request,
+                }
+            }
+            None => RoomToSession::Error {
+                code: ErrorCode::RoomFull,
+                message: format!("{room} is full"),
+                request,
+            },
+        }
+    }
+
+    /// takes a session off the waitlist, returns `false` if it was not waiting
+    pub fn leave_waitlist(&mut self, session_id: SessionId, request: Option<RequestId>) -> bool {
+        let left = self.waitlist.as_mut().and_then(|waitlist| {
+            let position = waitlist.iter().position(|w| w.participant.session_id == session_id)?;
+            waitlist.remove(position)
+        });
+        match left {
+            Some(waiting) => {
+                log::debug!("{} leaves the waitlist of {:?}", waiting.participant.username, self.id);
+                if let Some(participant_addr) = waiting.participant.addr.upgrade() {
+                    if let Err(error) = participant_addr.send(RoomToSession::Left {
+                        room: self.id.clone(),
+                        request,
+                    }) {
+                        log::warn!("failed to send Left {error}");
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// lets waiting sessions join while there are free spots
+    async fn admit_waiting(&mut self, ctx: &mut Context<Self>) {
+        while !self.is_full() {
+            let next = match self.waitlist.as_mut().and_then(VecDeque::pop_front) {
+                Some(next) => next,
+                None => break,
+            };
+            if !next.participant.is_gone() {
+                log::debug!("admitting {} to {:?}", next.participant.username, self.id);
+                // the join request was already answered with `Waitlisted`
+                self.add_participant(next.participant, next.password, None, ctx).await;
+            }
+        }
+    }
+
+    pub async fn remove_participant(
+        &mut self,
+        session_id: SessionId,
+        request: Option<RequestId>,
+        ctx: &mut Context<Self>,
+    ) {
+        if let Some(participant) = self.roster.remove(&session_id) {
+            log::debug!("{} leaves {:?}", participant.profile.full_name, self.id);
+            if let Some(participant_addr) = participant.addr.upgrade() {
+                if let Err(error) = participant_addr.send(RoomToSession::Left {
+                    room: self.id.clone(),
+                    request,
+                }) {
+                    log::warn!("failed to send Left {error}");
+                }
+            }
+            self.broadcast_event(RoomEvent::ParticipantLeft {
+                name: participant.profile.full_name,
+            });
+        } else {
+            log::warn!("{session_id} is not a participant of {:?}", self.id);
+        }
+        self.admit_waiting(ctx).await;
+        self.mark_if_empty();
+        log::debug!("room {:?} has {} participants", self.id, self.roster.len())
+    }
+
+    /// tells all participants and waiting sessions the room is closed and stops it
+    pub fn close(&mut self, ctx: &mut Context<Self>) {
+        log::info!("closing {:?}", self.id);
+        let waiting = self.waitlist.iter_mut().flat_map(|waitlist| waitlist.drain(..));
+        let participants = self
+            .roster
+            .drain()
+            .map(|(_, p)| p)
+            .chain(waiting.map(|w| w.participant));
+        for participant in participants.filter_map(|p| p.addr.upgrade()) {
+            if let Err(error) = participant.send(RoomToSession::Closed { room: self.id.clone() }) {
+                log::warn!("failed to send Closed {error}");
+            }
+        }
+        ctx.stop(None);
+    }
+
+    pub fn send_roster(&self, session_id: SessionId, request: Option<RequestId>) {
+        let roster = self.roster.values().map(Participant::from).collect();
+        self.send_to(
+            &session_id,
+            RoomToSession::RoomState {
+                room: self.id.clone(),
+                roster,
+                request,
+            },
+        );
+    }
+
+    pub async fn send_history(
+        &self,
+        session_id: SessionId,
+        request: Option<RequestId>,
+        before: Option<Uuid>,
+        limit: usize,
+    ) {
+        let messages = self.history_page(before, limit).await;
+        self.send_to(
+            &session_id,
+            RoomToSession::History {
+                room: self.id.clone(),
+                messages,
+                request,
+            },
+        );
+    }
+
+    /// passes `signal` on to `to` if both are participants
+    pub fn relay_signal(&self, from: SessionId, to: SessionId, request: Option<RequestId>, signal: Signal) {
+        if self.is_participant(&to) {
+            log::trace!("relaying {signal:?} from {from} to {to}");
+            self.send_to(
+                &to,
+                RoomToSession::Signal {
+                    room: self.id.clone(),
+                    from,
+                    signal,
+                },
+            );
+        } else {
+            self.send_error(
+                from,
+                request,
+                ErrorCode::PeerNotFound,
+                format!("{to} is not a participant of {}", self.id),
+            );
+        }
+    }
+
+    /// tells a participant that its command could not be carried out
+    fn send_error(
+        &self,
+        session_id: SessionId,
+        request: Option<RequestId>,
+        code: ErrorCode,
+        message: impl Into<String>,
+    ) {
+        self.send_to(
+            &session_id,
+            RoomToSession::Error {
+                code,
+                message: message.into(),
+                request,
+            },
+        );
+    }
+
+    /// up to `limit` messages older than `before`, oldest first
+    async fn history_page(&self, before: Option<Uuid>, limit: usize) -> Vec<ChatMessage> {
+        self.history
+            .page(before, limit.min(MAX_HISTORY_PAGE))
+            .await
+            .unwrap_or_else(|error| {
+                log::error!("failed to read history of {:?} {error}", self.id);
+                Vec::new()
+            })
+    }
+
+    fn broadcast_event(&self, event: RoomEvent) {
+        self.broadcast(|room| RoomToSession::RoomEvent {
+            room,
+            event: event.clone(),
+        });
+    }
+
+    /// sends `message` to a single participant
+    fn send_to(&self, session_id: &SessionId, message: RoomToSession) {
+        match self.roster.get(session_id).and_then(|p| p.addr.upgrade()) {
+            Some(participant_addr) => {
+                if let Err(error) = participant_addr.send(message) {
+                    log::warn!("failed to send to {session_id} {error}");
+                }
+            }
+            None => log::warn!("{session_id} is not a participant of {:?}", self.id),
+        }
+    }
+
+    /// sends a message built by `to_session` to every participant
+    fn broadcast(&self, to_session: impl Fn(RoomId) -> RoomToSession) {
+        for participant in self.roster.values().filter_map(|p| p.addr.upgrade()) {
+            if let Err(error) = participant.send(to_session(self.id.clone())) {
+                log::warn!("failed to broadcast to {:?} {error}", self.id);
+            }
+        }
+    }
+
+    pub async fn forward_to_participants(&mut self, message: ChatMessage, _ctx: &mut Context<Self>) {
+        self.store_message(&message).await;
+        self.mark_sent(&message).await;
         for participant in self.roster.iter().filter_map(|(_, p)| p.addr.upgrade()) {
             participant
                 .send(RoomToSession::ChatMessage {
@@ -60,10 +419,54 @@ impl Room {
         }
     }
 
-    fn store_message(&mut self, message: &ChatMessage) {
-        if self.history.len() == self.capacity {
-            self.history.pop_front();
+    async fn store_message(&mut self, message: &ChatMessage) {
+        if let Err(error) = self.history.push(message).await {
+            log::error!("failed to store message in {:?} {error}", self.id);
+        }
+    }
+}
+
+/// garbage collection
+impl Room {
+    /// removes participants whose session has stopped without leaving
+    async fn gc(&mut self, ctx: &mut Context<Self>) {
+        let gone = self
+            .roster
+            .iter()
+            .filter(|(_, participant)| participant.is_gone())
+            .map(|(session_id, _)| *session_id)
+            .collect::<Vec<_>>();
+
+        for session_id in gone {
+            if let Some(participant) = self.roster.remove(&session_id) {
+                log::debug!("{} is gone from {:?}", participant.profile.full_name, self.id);
+                self.broadcast_event(RoomEvent::ParticipantLeft {
+                    name: participant.profile.full_name,
+                });
+            }
+        }
+        if let Some(waitlist) = self.waitlist.as_mut() {
+            waitlist.retain(|waiting| !waiting.participant.is_gone());
+        }
+        self.admit_waiting(ctx).await;
+        self.mark_if_empty();
+    }
+
+    fn mark_if_empty(&mut self) {
+        if self.roster.is_empty() && self.empty_since.is_none() {
+            self.empty_since = Some(Instant::now());
+        }
+    }
+
+    /// stops the room once it has been empty for longer than `idle_timeout`, returns whether it did
+    fn close_if_idle(&mut self, ctx: &mut Context<Self>) -> bool {
+        if let (Some(idle_timeout), Some(empty_since)) = (self.idle_timeout, self.empty_since) {
+            if empty_since.elapsed() > idle_timeout {
+                log::debug!("{:?} has been empty for {:?}, stopping", self.id, empty_since.elapsed());
+                ctx.stop(None);
+                return true;
+            }
         }
-        self.history.push_back(message.to_owned());
+        false
     }
 }
@@ -0,0 +1,195 @@
+use hannibal::Context;
+use signaler_protocol::{Access, ErrorCode, RequestId, Role, RoomEvent, RoomSettings};
+use tracing::log;
+
+use crate::{history::RoomRecord, session::SessionId};
+
+use super::{command::RoomToSession, Room, RoomParticipant};
+
+/// moderation
+impl Room {
+    pub fn is_muted(&self, session_id: &SessionId) -> bool {
+        self.roster.get(session_id).map(|p| p.muted).unwrap_or_default()
+    }
+
+    /// what `username` may do here, whether it is currently a participant or not
+    pub fn role_of(&self, username: &str) -> Role {
+        if username == self.owner {
+            Role::Owner
+        } else {
+            self.roles.get(username).copied().unwrap_or(Role::Member)
+        }
+    }
+
+    /// `by` may only moderate participants it outranks
+    fn check_outranks(&self, by: &SessionId, target: &SessionId) -> Result<(), ErrorCode> {
+        let by = self.roster.get(by).ok_or(ErrorCode::NotAMember)?;
+        let target = self.roster.get(target).ok_or(ErrorCode::PeerNotFound)?;
+        if by.role > target.role {
+            Ok(())
+        } else {
+            Err(ErrorCode::Forbidden)
+        }
+    }
+
+    pub async fn kick(
+        &mut self,
+        by: SessionId,
+        target: SessionId,
+        request: Option<RequestId>,
+        ctx: &mut Context<Self>,
+    ) {
+        if let Err(code) = self.check_outranks(&by, &target) {
+            self.send_error(by, request, code, "not allowed to kick");
+            return;
+        }
+        if let Some(name) = self.expel(target) {
+            self.broadcast_event(RoomEvent::ParticipantKicked { name });
+        }
+        self.admit_waiting(ctx).await;
+    }
+
+    pub async fn ban(&mut self, by: SessionId, target: SessionId, request: Option<RequestId>, ctx: &mut Context<Self>) {
+        if let Err(code) = self.check_outranks(&by, &target) {
+            self.send_error(by, request, code, "not allowed to ban");
+            return;
+        }
+        let username = match self.roster.get(&target) {
+            Some(participant) => participant.username.clone(),
+            None => return,
+        };
+        self.bans.insert(username.clone());
+        self.save_record().await;
+        if let Some(name) = self.expel_user(&username) {
+            self.broadcast_event(RoomEvent::ParticipantBanned { name });
+        }
+        self.admit_waiting(ctx).await;
+    }
+
+    pub fn set_muted(&mut self, by: SessionId, target: SessionId, muted: bool, request: Option<RequestId>) {
+        if let Err(code) = self.check_outranks(&by, &target) {
+            self.send_error(by, request, code, "not allowed to mute");
+            return;
+        }
+        let (username, name) = match self.roster.get(&target) {
+            Some(participant) => (participant.username.clone(), participant.profile.full_name.clone()),
+            None => return,
+        };
+        if muted {
+            self.mutes.insert(username.clone());
+        } else {
+            self.mutes.remove(&username);
+        }
+        for participant in self.sessions_of(&username) {
+            participant.muted = muted;
+        }
+        self.broadcast_event(if muted {
+            RoomEvent::ParticipantMuted { name }
+        } else {
+            RoomEvent::ParticipantUnmuted { name }
+        });
+    }
+
+    /// only the owner may change roles, and there is only one owner
+    pub fn set_role(&mut self, by: SessionId, target: SessionId, role: Role, request: Option<RequestId>) {
+        if let Err(code) = self.check_outranks(&by, &target) {
+            self.send_error(by, request, code, "not allowed to change roles");
+            return;
+        }
+        let by_owner = self
+            .roster
+            .get(&by)
+            .map(|p| p.username == self.owner)
+            .unwrap_or_default();
+        if !by_owner || role == Role::Owner {
+            self.send_error(by, request, ErrorCode::Forbidden, "not allowed to change roles");
+            return;
+        }
+        let (username, name) = match self.roster.get(&target) {
+            Some(participant) => (participant.username.clone(), participant.profile.full_name.clone()),
+            None => return,
+        };
+        if role == Role::Member {
+            self.roles.remove(&username);
+        } else {
+            self.roles.insert(username.clone(), role);
+        }
+        for participant in self.sessions_of(&username) {
+            participant.role = role;
+        }
+        self.broadcast_event(RoomEvent::RoleChanged { name, role });
+    }
+
+    /// adds `username` to the invite list, requires being at least a moderator
+    pub async fn invite(&mut self, by: SessionId, username: String, request: Option<RequestId>) {
+        let allowed = self
+            .roster
+            .get(&by)
+            .map(|p| p.role >= Role::Moderator)
+            .unwrap_or_default();
+        if !allowed {
+            self.send_error(by, request, ErrorCode::Forbidden, "not allowed to invite");
+            return;
+        }
+        match &mut self.access {
+            Access::InviteOnly { invited } => {
+                log::debug!("{username} is invited to {:?}", self.id);
+                if !invited.contains(&username) {
+                    invited.push(username);
+                    self.save_record().await;
+                }
+            }
+            _ => self.send_error(by, request, ErrorCode::UnsupportedCommand, "room is not invite only"),
+        }
+    }
+
+    /// keeps the invite list and bans for when the room is recreated from its history
+    async fn save_record(&mut self) {
+        let record = RoomRecord {
+            owner: self.owner.clone(),
+            settings: RoomSettings {
+                access: self.access.clone(),
+                max_participants: self.max_participants,
+                waitlist: self.waitlist.is_some(),
+            },
+            bans: self.bans.iter().cloned().collect(),
+        };
+        if let Err(error) = self.history.save_room(&record).await {
+            log::error!("failed to save settings of {:?} {error}", self.id);
+        }
+    }
+
+    /// every participant that is a session of `username`
+    fn sessions_of<'a>(&'a mut self, username: &'a str) -> impl Iterator<Item = &'a mut RoomParticipant> {
+        self.roster
+            .values_mut()
+            .filter(move |participant| participant.username == username)
+    }
+
+    /// expels every session of `username`, returns its name
+    fn expel_user(&mut self, username: &str) -> Option<String> {
+        let sessions = self
+            .roster
+            .iter()
+            .filter(|(_, participant)| participant.username == username)
+            .map(|(session_id, _)| *session_id)
+            .collect::<Vec<_>>();
+        sessions
+            .into_iter()
+            .filter_map(|session_id| self.expel(session_id))
+            .last()
+    }
+
+    /// removes a participant against its will, returns its name
+    fn expel(&mut self, session_id: SessionId) -> Option<String> {
+        let participant = self.roster.remove(&session_id)?;
+        log::debug!("{} was expelled from {:?}", participant.profile.full_name, self.id);
+        if let Some(participant_addr) = participant.addr.upgrade() {
+            if let Err(error) = participant_addr.send(RoomToSession::Kicked { room: self.id.clone() }) {
+                log::warn!("failed to send Kicked {error}");
+            }
+        }
+        self.mark_if_empty();
+        Some(participant.profile.full_name)
+    }
+}
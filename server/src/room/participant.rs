@@ -1,11 +1,26 @@
 use hannibal::WeakAddr;
+use signaler_protocol::{Participant, Role, UserProfile};
 
 use crate::session::{Session, SessionId};
 
 pub struct RoomParticipant {
     pub session_id: SessionId,
     pub addr: WeakAddr<Session>,
-    pub profile: String,
+    pub username: String,
+    pub profile: UserProfile,
+    pub role: Role,
+    /// may not send messages
+    pub muted: bool,
+}
+
+impl RoomParticipant {
+    /// the session has stopped
+    pub fn is_gone(&self) -> bool {
+        match self.addr.upgrade() {
+            Some(addr) => addr.stopped(),
+            None => true,
+        }
+    }
 }
 
 impl std::fmt::Debug for RoomParticipant {
@@ -13,7 +28,20 @@ impl std::fmt::Debug for RoomParticipant {
         f.debug_struct("RoomParticipant")
             .field("session_id", &self.session_id)
             .field("addr", &"???".to_string())
+            .field("username", &self.username)
             .field("profile", &self.profile)
+            .field("role", &self.role)
+            .field("muted", &self.muted)
             .finish()
     }
 }
+
+impl From<&RoomParticipant> for Participant {
+    fn from(participant: &RoomParticipant) -> Participant {
+        Participant::from((
+            participant.profile.clone(),
+            participant.session_id.into(),
+            participant.role,
+        ))
+    }
+}
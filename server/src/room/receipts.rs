@@ -0,0 +1,74 @@
+use signaler_protocol::{ChatMessage, ErrorCode, RequestId};
+use tracing::log;
+use uuid::Uuid;
+
+use crate::session::SessionId;
+
+use super::{command::RoomToSession, Room};
+
+/// read markers
+impl Room {
+    /// moves the read marker of the participant forward to `up_to`, optionally telling its sender
+    pub async fn mark_read(&mut self, session_id: SessionId, up_to: Uuid, receipt: bool, request: Option<RequestId>) {
+        let message = match self.stored_message(up_to).await {
+            Ok(message) => message,
+            Err(code) => {
+                self.send_error(session_id, request, code, format!("can't mark {up_to} as read"));
+                return;
+            }
+        };
+        let username = match self.roster.get(&session_id) {
+            Some(participant) => participant.username.clone(),
+            None => return,
+        };
+        match self.history.mark_read(&username, up_to).await {
+            Ok(true) => log::trace!("{username} read up to {up_to} in {:?}", self.id),
+            Ok(false) => {
+                log::trace!("{username} has already read past {up_to} in {:?}", self.id);
+                return;
+            }
+            Err(error) => {
+                log::error!("failed to store read marker in {:?} {error}", self.id);
+                self.send_error(
+                    session_id,
+                    request,
+                    ErrorCode::Internal,
+                    format!("can't mark {up_to} as read"),
+                );
+                return;
+            }
+        }
+
+        let sender: SessionId = message.sender.into();
+        if !receipt || sender == session_id || !self.is_participant(&sender) {
+            return;
+        }
+        self.send_to(
+            &sender,
+            RoomToSession::ReadReceipt {
+                room: self.id.clone(),
+                uuid: up_to,
+                reader: session_id,
+            },
+        );
+    }
+
+    /// sending a message marks everything up to it as read for the sender
+    pub async fn mark_sent(&mut self, message: &ChatMessage) {
+        let sender: SessionId = message.sender.clone().into();
+        let username = match self.roster.get(&sender) {
+            Some(participant) => participant.username.clone(),
+            None => return,
+        };
+        if let Err(error) = self.history.mark_read(&username, message.uuid).await {
+            log::error!("failed to store read marker in {:?} {error}", self.id);
+        }
+    }
+
+    pub async fn unread(&self, username: &str) -> usize {
+        self.history.unread(username).await.unwrap_or_else(|error| {
+            log::error!("failed to count unread messages in {:?} {error}", self.id);
+            0
+        })
+    }
+}
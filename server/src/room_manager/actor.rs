@@ -2,6 +2,7 @@ use std::time::Duration;
 
 use async_trait::async_trait;
 use hannibal::{Actor, Handler};
+use signaler_protocol::{ErrorCode, RoomInfo};
 use tracing::log;
 
 use crate::metrics::MetricsService;
@@ -22,6 +23,7 @@ impl Actor for RoomManager {
     }
     async fn stopped(&mut self, _ctx: &mut hannibal::Context<Self>) {
         log::trace!("shutting down");
+        self.drain().await;
     }
 }
 
@@ -30,15 +32,53 @@ impl Handler<Command> for RoomManager {
     async fn handle(&mut self, _ctx: &mut hannibal::Context<Self>, cmd: Command) {
         log::trace!("received command {:?}", cmd);
         match cmd {
-            Command::JoinRoom { room_id, participant } => self.join_room(&room_id, participant).await,
+            Command::JoinRoom {
+                room_id,
+                participant,
+                password,
+                request,
+            } => self.join_room(&room_id, participant, password, request).await,
+            Command::SetIdleTimeout(idle_timeout) => {
+                log::debug!("closing rooms after {idle_timeout:?} without participants");
+                self.idle_timeout = Some(idle_timeout);
+            }
+            Command::SetMaxParticipants(max_participants) => {
+                log::debug!("limiting rooms to {max_participants} participants by default");
+                self.max_participants = Some(max_participants);
+            }
+            Command::UseHistoryDatabase(database) => {
+                log::debug!("keeping room histories in {}", database.display());
+                self.history_database = Some(database);
+            }
         }
     }
 }
 
+#[async_trait::async_trait]
+impl Handler<CreateRoom> for RoomManager {
+    async fn handle(&mut self, _ctx: &mut hannibal::Context<Self>, msg: CreateRoom) -> Result<(), ErrorCode> {
+        self.create_and_join_room(msg).await
+    }
+}
+
+#[async_trait::async_trait]
+impl Handler<CloseRoom> for RoomManager {
+    async fn handle(&mut self, _ctx: &mut hannibal::Context<Self>, msg: CloseRoom) -> Result<(), ErrorCode> {
+        self.close_room(msg).await
+    }
+}
+
+#[async_trait::async_trait]
+impl Handler<ListRooms> for RoomManager {
+    async fn handle(&mut self, _ctx: &mut hannibal::Context<Self>, _: ListRooms) -> Vec<RoomInfo> {
+        self.list_rooms().await
+    }
+}
+
 #[async_trait::async_trait]
 impl Handler<Gc> for RoomManager {
     async fn handle(&mut self, ctx: &mut hannibal::Context<Self>, _: Gc) {
-        self.gc(ctx);
+        self.gc(ctx).await;
     }
 }
 
@@ -1,6 +1,8 @@
-use signaler_protocol::RoomId;
+use std::{path::PathBuf, time::Duration};
 
-use crate::room::participant::RoomParticipant;
+use signaler_protocol::{ErrorCode, RequestId, RoomId, RoomInfo, RoomSettings};
+
+use crate::{room::participant::RoomParticipant, session::SessionId};
 
 #[hannibal::message]
 #[derive(Debug)]
@@ -8,9 +10,39 @@ pub enum Command {
     JoinRoom {
         room_id: RoomId,
         participant: RoomParticipant,
+        password: Option<String>,
+        request: Option<RequestId>,
     },
+    /// how long new rooms stay open without participants
+    SetIdleTimeout(Duration),
+    /// default limit for rooms that don't set their own
+    SetMaxParticipants(usize),
+    /// persist room histories in this SQLite database
+    UseHistoryDatabase(PathBuf),
 }
 
+/// creates a room and adds its creator as owner
+#[hannibal::message(result = "Result<(), ErrorCode>")]
+#[derive(Debug)]
+pub struct CreateRoom {
+    pub room_id: RoomId,
+    pub settings: RoomSettings,
+    pub participant: RoomParticipant,
+}
+
+#[hannibal::message(result = "Result<(), ErrorCode>")]
+#[derive(Debug)]
+pub struct CloseRoom {
+    pub room_id: RoomId,
+    pub session_id: SessionId,
+    pub username: String,
+    pub is_admin: bool,
+}
+
+#[hannibal::message(result = "Vec<RoomInfo>")]
+#[derive(Debug)]
+pub struct ListRooms;
+
 #[hannibal::message]
 #[derive(Clone, Copy, Debug)]
 pub struct Gc;
@@ -1,10 +1,14 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 
-use hannibal::{Actor, Addr, Context, WeakAddr};
+use hannibal::{Actor, Addr, Context, Service, WeakAddr};
 use prometheus::IntGauge;
+use signaler_protocol::{ErrorCode, RequestId, RoomInfo, RoomSettings};
 use tracing::log;
 
-use crate::room::{self, participant::RoomParticipant, Room, RoomId};
+use crate::{
+    history::{HistoryStore, InMemoryHistory, RoomRecord, SqliteHistory},
+    room::{self, participant::RoomParticipant, Room, RoomId},
+};
 
 mod actor;
 pub mod command;
@@ -15,49 +19,200 @@ pub use command::Command;
 pub struct RoomManager {
     pub rooms: HashMap<RoomId, Addr<Room>>,
     open_rooms: Option<IntGauge>,
+    /// rooms stay open forever if unset
+    idle_timeout: Option<Duration>,
+    /// rosters are unbounded if unset
+    max_participants: Option<usize>,
+    /// histories are kept in memory if unset
+    history_database: Option<PathBuf>,
 }
 
 impl RoomManager {
-    async fn join_room(&mut self, room: &RoomId, participant: RoomParticipant) {
+    pub async fn get_room_list() -> hannibal::Result<Vec<RoomInfo>> {
+        let rooms = Self::from_registry().await?.call(command::ListRooms).await?;
+        Ok(rooms)
+    }
+
+    async fn join_room(
+        &mut self,
+        room: &RoomId,
+        participant: RoomParticipant,
+        password: Option<String>,
+        request: Option<RequestId>,
+    ) {
         log::debug!("join {room} with {participant:?}");
-        let existing_room = self.rooms.get(room).cloned();
+        let existing_room = self.open_room(room);
         let new_room = if existing_room.is_none() {
             log::trace!("no room found {:?}, creating", existing_room);
-            self.create_room(room).await.upgrade()
+            let (history, saved) = self.open_history(room).await;
+            let record = saved.unwrap_or_else(|| RoomRecord {
+                owner: participant.username.clone(),
+                settings: RoomSettings::default(),
+                bans: Vec::new(),
+            });
+            self.create_room(room, record, history).await.upgrade()
         } else {
             None
         };
 
         if let Some(room) = existing_room.or(new_room) {
-            if let Err(error) = room.send(room::Command::AddParticipant { participant }) {
+            if let Err(error) = room.send(room::Command::AddParticipant {
+                participant,
+                password,
+                request,
+            }) {
                 log::error!("failed to add participant to room {}", error)
             }
         }
     }
 
-    async fn create_room(&mut self, name: &str) -> WeakAddr<Room> {
-        log::debug!("create room: {:?}", name);
-        let room = Room::new(name.into()).start().await.unwrap();
+    async fn create_and_join_room(&mut self, msg: command::CreateRoom) -> Result<(), ErrorCode> {
+        if self.open_room(&msg.room_id).is_some() {
+            return Err(ErrorCode::RoomExists);
+        }
+        if msg.settings.max_participants == Some(0) {
+            log::debug!("refusing to create {} without room for participants", msg.room_id);
+            return Err(ErrorCode::InvalidCommand);
+        }
+        let (history, saved) = self.open_history(&msg.room_id).await;
+        let bans = match saved {
+            Some(saved) if saved.owner != msg.participant.username => {
+                log::debug!("{} belongs to {}", msg.room_id, saved.owner);
+                return Err(ErrorCode::RoomExists);
+            }
+            Some(saved) => saved.bans,
+            None => Vec::new(),
+        };
+        let record = RoomRecord {
+            owner: msg.participant.username.clone(),
+            settings: msg.settings,
+            bans,
+        };
+        let room = self
+            .create_room(&msg.room_id, record, history)
+            .await
+            .upgrade()
+            .ok_or(ErrorCode::Internal)?;
+        // the session answers the request with `RoomCreated`
+        room.send(room::Command::AddParticipant {
+            participant: msg.participant,
+            password: None,
+            request: None,
+        })
+        .map_err(|error| {
+            log::error!("failed to add owner to room {}", error);
+            ErrorCode::Internal
+        })
+    }
+
+    /// the room unless it has stopped
+    fn open_room(&self, room_id: &RoomId) -> Option<Addr<Room>> {
+        self.rooms.get(room_id).filter(|room| !room.stopped()).cloned()
+    }
+
+    async fn create_room(
+        &mut self,
+        name: &str,
+        mut record: RoomRecord,
+        mut history: Box<dyn HistoryStore>,
+    ) -> WeakAddr<Room> {
+        log::debug!("create room: {:?} {:?}", name, record);
+        record.settings.max_participants = record.settings.max_participants.or(self.max_participants);
+        if let Err(error) = history.save_room(&record).await {
+            log::error!("failed to save settings of {name:?} {error}");
+        }
+        let room = Room::new(name.into(), record, self.idle_timeout, history)
+            .start()
+            .await
+            .unwrap();
         let weak_room = room.downgrade();
-        self.rooms.insert(name.into(), room);
-        if let Some(gauge) = self.open_rooms.as_ref() {
-            gauge.inc();
-            log::trace!("increasing rooms count {:?}", gauge.get());
+        if self.rooms.insert(name.into(), room).is_none() {
+            if let Some(gauge) = self.open_rooms.as_ref() {
+                gauge.inc();
+                log::trace!("increasing rooms count {:?}", gauge.get());
+            }
         }
         weak_room
     }
 
-    // fn list_rooms(&self) -> Vec<String> {
-    //     self.rooms.keys().map(ToString::to_string).collect()
-    // }
+    /// picks up where a previous room of the same name left off, if histories are persisted
+    ///
+    /// along with the owner and settings saved with it, a history without them is not reused
+    async fn open_history(&self, name: &str) -> (Box<dyn HistoryStore>, Option<RoomRecord>) {
+        if let Some(database) = self.history_database.clone() {
+            match SqliteHistory::open(database, name.into()).await {
+                Ok(history) => match (history.room().await, history.count_after(None).await) {
+                    (Ok(Some(saved)), _) => return (Box::new(history), Some(saved)),
+                    (Ok(None), Ok(0)) => return (Box::new(history), None),
+                    (Ok(None), Ok(_)) => log::warn!("history of {name:?} has no saved settings, keeping it in memory"),
+                    (Err(error), _) | (_, Err(error)) => {
+                        log::error!("failed to read settings of {name:?}, keeping it in memory {error}")
+                    }
+                },
+                Err(error) => log::error!("failed to open history of {name:?}, keeping it in memory {error}"),
+            }
+        }
+        (Box::new(InMemoryHistory::default()), None)
+    }
+
+    async fn list_rooms(&self) -> Vec<RoomInfo> {
+        let mut infos = Vec::with_capacity(self.rooms.len());
+        for (room_id, room) in &self.rooms {
+            match room.call(room::command::GetInfo).await {
+                Ok(Some(info)) => infos.push(info),
+                Ok(None) => {}
+                Err(error) => log::warn!("failed to get info from {room_id} {error}"),
+            }
+        }
+        infos
+    }
 
-    // fn close_room(&mut self, room_id: RoomId) -> bool {
-    //     self.rooms.remove(&room_id).is_some()
-    // }
+    async fn close_room(&mut self, msg: command::CloseRoom) -> Result<(), ErrorCode> {
+        let room_id = &msg.room_id;
+        let room = self.rooms.get(room_id).ok_or(ErrorCode::RoomNotFound)?;
+        let closed = room
+            .call(room::command::Close {
+                session_id: msg.session_id,
+                username: msg.username,
+                is_admin: msg.is_admin,
+            })
+            .await
+            .map_err(|error| {
+                log::warn!("failed to close {room_id} {error}");
+                ErrorCode::RoomNotFound
+            })?;
+        if !closed {
+            return Err(ErrorCode::Forbidden);
+        }
+
+        log::debug!("closed room {room_id}");
+        if self.rooms.remove(room_id).is_some() {
+            if let Some(gauge) = self.open_rooms.as_ref() {
+                gauge.dec();
+                log::trace!("decreasing rooms count {:?}", gauge.get());
+            }
+        }
+        Ok(())
+    }
 }
 
 impl RoomManager {
-    fn gc(&mut self, _ctx: &mut Context<Self>) {
+    /// stops all rooms
+    async fn drain(&mut self) {
+        for (id, mut room) in self.rooms.drain() {
+            log::trace!("stopping room {}", id);
+            if let Err(error) = room.stop(None) {
+                log::warn!("failed to stop room {} {}", id, error);
+            } else {
+                room.wait_for_stop().await;
+            }
+        }
+    }
+
+    /// forgets stopped rooms and closes idle ones
+    ///
+    /// rooms don't stop themselves, a join that is already queued would be lost
+    async fn gc(&mut self, _ctx: &mut Context<Self>) {
         // log::trace!("gc");
         self.rooms.retain(|id, room| {
             if room.stopped() {
@@ -71,5 +226,23 @@ impl RoomManager {
                 true
             }
         });
+
+        let mut idle = Vec::new();
+        for (id, room) in &self.rooms {
+            match room.call(room::command::CloseIfIdle).await {
+                Ok(true) => idle.push(id.clone()),
+                Ok(false) => {}
+                Err(error) => log::warn!("failed to check whether {id} is idle {error}"),
+            }
+        }
+        for id in idle {
+            log::debug!("closed idle room {id}");
+            if self.rooms.remove(&id).is_some() {
+                if let Some(gauge) = self.open_rooms.as_ref() {
+                    gauge.dec();
+                    log::trace!("decreasing rooms count {:?}", gauge.get());
+                }
+            }
+        }
     }
 }
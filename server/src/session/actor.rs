@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use async_trait::async_trait;
 use hannibal::{Actor, Handler};
-use signaler_protocol::SessionMessage;
+use signaler_protocol::{Presence, SessionMessage};
 use tracing::log;
 
 use crate::room::command::RoomToSession;
@@ -14,10 +14,13 @@ impl Actor for Session {
     async fn started(&mut self, ctx: &mut hannibal::Context<Self>) -> hannibal::Result<()> {
         log::info!("starting session on actor {:?}", ctx.actor_id());
         ctx.send_interval(Gc, Duration::from_secs(5));
+        self.publish_presence(self.presence.clone());
         Ok(())
     }
-    async fn stopped(&mut self, _ctx: &mut hannibal::Context<Self>) {
+    async fn stopped(&mut self, ctx: &mut hannibal::Context<Self>) {
         log::debug!("shutting down Session");
+        self.publish_presence(Presence::Offline);
+        self.leave_all_rooms(ctx);
     }
 }
 
@@ -25,7 +28,7 @@ impl Actor for Session {
 impl Handler<Command> for Session {
     async fn handle(&mut self, ctx: &mut hannibal::Context<Self>, cmd: Command) {
         log::trace!("received command {:?}", cmd);
-        self.dispatch_command(cmd.0, ctx).await;
+        self.dispatch_command(cmd.id, cmd.command, ctx).await;
     }
 }
 
@@ -34,6 +37,7 @@ impl Handler<RoomToSession> for Session {
     async fn handle(&mut self, _ctx: &mut hannibal::Context<Self>, msg: RoomToSession) {
         match msg {
             RoomToSession::Joined(room_id, room_addr) => {
+                self.waiting.remove(&room_id);
                 if self.rooms.insert(room_id.clone(), room_addr).is_some() {
                     log::warn!("received redundant Joined from {room_id:?}")
                 }
@@ -41,10 +45,142 @@ impl Handler<RoomToSession> for Session {
             RoomToSession::ChatMessage { room, message } => {
                 self.send_to_connection(SessionMessage::Message { message, room }.into());
             }
+            RoomToSession::MessageEdited { room, message } => {
+                self.send_to_connection(SessionMessage::MessageEdited { message, room }.into());
+            }
+            RoomToSession::MessageDeleted { room, uuid } => {
+                self.send_to_connection(SessionMessage::MessageDeleted { uuid, room }.into());
+            }
+            RoomToSession::Ephemeral {
+                room,
+                from,
+                signal,
+                active,
+            } => {
+                self.send_to_connection(
+                    SessionMessage::Ephemeral {
+                        room,
+                        from: from.into(),
+                        signal,
+                        active,
+                    }
+                    .into(),
+                );
+            }
+            RoomToSession::Reactions { room, uuid, reactions } => {
+                self.send_to_connection(SessionMessage::Reactions { uuid, room, reactions }.into());
+            }
+            RoomToSession::ReadReceipt { room, uuid, reader } => {
+                self.send_to_connection(
+                    SessionMessage::ReadReceipt {
+                        room,
+                        uuid,
+                        reader: reader.into(),
+                    }
+                    .into(),
+                );
+            }
+            RoomToSession::History {
+                room,
+                messages,
+                request,
+            } => {
+                self.reply(request, SessionMessage::History { room, messages });
+            }
+            RoomToSession::Thread {
+                room,
+                root,
+                messages,
+                request,
+            } => {
+                self.reply(request, SessionMessage::Thread { room, root, messages });
+            }
+            RoomToSession::RoomState { room, roster, request } => {
+                self.reply(
+                    request,
+                    SessionMessage::RoomParticipants {
+                        room,
+                        participants: roster,
+                    },
+                );
+            }
+            RoomToSession::RoomEvent { room, event } => {
+                self.send_to_connection(SessionMessage::RoomEvent { room, event }.into());
+            }
+            RoomToSession::Signal { room, from, signal } => {
+                self.send_to_connection(signal.into_session_message(room, from).into());
+            }
+            RoomToSession::Error { code, message, request } => self.send_error(request, code, message),
+            RoomToSession::Kicked { room } => {
+                self.rooms.remove(&room);
+                self.send_to_connection(SessionMessage::Kicked { room }.into());
+            }
+            RoomToSession::Closed { room } => {
+                self.rooms.remove(&room);
+                self.waiting.remove(&room);
+                self.send_to_connection(SessionMessage::RoomClosed { room }.into());
+            }
+            RoomToSession::JoinDeclined { room, reason, request } => {
+                self.reply(request, SessionMessage::JoinDeclined { room, reason });
+            }
+            RoomToSession::Waitlisted {
+                room,
+                addr,
+                position,
+                request,
+            } => {
+                self.waiting.insert(room.clone(), addr);
+                self.reply(request, SessionMessage::Waitlisted { room, position });
+            }
+            RoomToSession::Left { room, request } => {
+                if self
+                    .rooms
+                    .remove(&room)
+                    .or_else(|| self.waiting.remove(&room))
+                    .is_none()
+                {
+                    log::warn!("received Left from {room:?} without having joined")
+                }
+                self.reply(request, SessionMessage::Left { room });
+            }
         }
     }
 }
 
+#[async_trait::async_trait]
+impl Handler<Direct> for Session {
+    async fn handle(&mut self, _ctx: &mut hannibal::Context<Self>, msg: Direct) {
+        self.send_to_connection(
+            SessionMessage::Direct {
+                from: msg.from.into(),
+                payload: msg.payload,
+            }
+            .into(),
+        );
+    }
+}
+
+#[async_trait::async_trait]
+impl Handler<PresenceChanged> for Session {
+    async fn handle(&mut self, _ctx: &mut hannibal::Context<Self>, msg: PresenceChanged) {
+        self.send_to_connection(
+            SessionMessage::Presence {
+                username: msg.username,
+                session: msg.session_id.into(),
+                presence: msg.presence,
+            }
+            .into(),
+        );
+    }
+}
+
+#[async_trait::async_trait]
+impl Handler<Reconnect> for Session {
+    async fn handle(&mut self, ctx: &mut hannibal::Context<Self>, msg: Reconnect) {
+        self.reconnect(msg.connection, msg.token, msg.request, ctx);
+    }
+}
+
 #[async_trait::async_trait]
 impl Handler<Gc> for Session {
     async fn handle(&mut self, ctx: &mut hannibal::Context<Self>, _: Gc) {
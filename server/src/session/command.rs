@@ -1,17 +1,51 @@
 use hannibal::message;
 use signaler_protocol as protocol;
+use uuid::Uuid;
 
-/// TODO: this is probably unnecessary
+use super::message::FromSession;
+
+/// a `SessionCommand` and the id of the `Request` it came in
 #[message]
 #[derive(Debug)]
-pub struct Command(pub protocol::SessionCommand);
+pub struct Command {
+    pub id: Option<protocol::RequestId>,
+    pub command: protocol::SessionCommand,
+}
 
 impl From<protocol::SessionCommand> for Command {
-    fn from(sc: protocol::SessionCommand) -> Self {
-        Self(sc)
+    fn from(command: protocol::SessionCommand) -> Self {
+        Self { id: None, command }
     }
 }
 
 #[message]
 #[derive(Clone, Copy, Debug)]
 pub struct Gc;
+
+/// payload sent by another session via `SessionCommand::Direct`
+#[message]
+#[derive(Debug)]
+pub struct Direct {
+    pub from: super::SessionId,
+    pub payload: serde_json::Value,
+}
+
+/// a session changed its presence
+///
+/// sent by the session to its rooms and the `SessionManager`, which pass it on to other sessions
+#[message]
+#[derive(Clone, Debug)]
+pub struct PresenceChanged {
+    pub session_id: super::SessionId,
+    pub username: String,
+    pub presence: protocol::Presence,
+}
+
+/// rebinds a session to a new connection
+#[message]
+pub struct Reconnect {
+    pub connection: hannibal::Sender<FromSession>,
+    pub token: Uuid,
+    /// id of the `Authenticate` request, echoed on the reply
+    pub request: Option<protocol::RequestId>,
+}
@@ -1,5 +1,5 @@
 use hannibal::WeakAddr;
-use signaler_protocol::SessionMessage;
+use signaler_protocol::{RequestId, SessionMessage};
 
 use super::Session;
 
@@ -7,7 +7,15 @@ use super::Session;
 #[derive(Debug)]
 pub enum FromSession {
     SessionMessage(SessionMessage),
-    SessionAssociated { session: WeakAddr<Session> },
+    /// direct reply to a `Request`
+    Reply {
+        id: RequestId,
+        message: SessionMessage,
+    },
+    /// the connection now belongs to `session`
+    SessionAssociated {
+        session: WeakAddr<Session>,
+    },
 }
 
 impl From<SessionMessage> for FromSession {
@@ -15,3 +23,13 @@ impl From<SessionMessage> for FromSession {
         Self::SessionMessage(inner)
     }
 }
+
+impl FromSession {
+    /// wraps `message` as a reply if it answers a request with an id
+    pub fn reply(id: Option<RequestId>, message: SessionMessage) -> Self {
+        match id {
+            Some(id) => Self::Reply { id, message },
+            None => Self::SessionMessage(message),
+        }
+    }
+}
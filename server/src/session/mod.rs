@@ -1,15 +1,21 @@
-use std::{collections::HashMap, time::Instant};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Instant,
+};
 
 use hannibal::{Context, Service, WeakAddr};
 use signaler_protocol as protocol;
-use signaler_protocol::RoomId;
+use signaler_protocol::{ErrorCode, RequestId, RoomId};
 use tracing::log;
 use uuid::Uuid;
 
-use crate::room::command::ChatRoomCommand;
+use crate::room::command::{ChatRoomCommand, GetUnread};
 use crate::{
+    credentials::Identity,
     room::{participant::RoomParticipant, Room},
     room_manager::{self, RoomManager},
+    session_manager::{self, SessionManager},
+    shutdown,
 };
 
 use self::message::FromSession;
@@ -20,98 +26,386 @@ pub mod message;
 
 pub type SessionId = Uuid;
 
+/// how many messages are kept for a disconnected session
+const MAX_PENDING_MESSAGES: usize = 500;
+
 pub struct Session {
     pub session_id: SessionId,
+    pub username: String,
+    pub profile: protocol::UserProfile,
+    pub is_admin: bool,
+    /// as set by the client, published as offline while disconnected
+    pub presence: protocol::Presence,
+    /// the presence rooms and subscribers were told about last
+    published_presence: Option<protocol::Presence>,
     pub connection: Option<hannibal::Sender<message::FromSession>>,
     pub last_seen_connected: Instant,
     pub rooms: HashMap<RoomId, WeakAddr<Room>>,
+    /// rooms this session is on the waitlist of, it may only leave those
+    pub waiting: HashMap<RoomId, WeakAddr<Room>>,
+
+    /// messages that could not be delivered while the connection was gone
+    pending: VecDeque<FromSession>,
 }
 
 impl std::fmt::Debug for Session {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Session")
             .field("session_id", &self.session_id)
+            .field("username", &self.username)
+            .field("profile", &self.profile)
+            .field("is_admin", &self.is_admin)
+            .field("presence", &self.presence)
             .field("connection is some?", &self.connection.is_some())
+            .field("pending", &self.pending.len())
             .finish()
     }
 }
 
-impl Default for Session {
-    fn default() -> Self {
+impl Session {
+    pub fn new(identity: Identity) -> Self {
         Session {
             session_id: Uuid::new_v4(),
+            username: identity.username,
+            profile: identity.profile,
+            is_admin: identity.is_admin,
+            presence: Default::default(),
+            published_presence: None,
             connection: None,
             last_seen_connected: Instant::now(),
             rooms: Default::default(),
+            waiting: Default::default(),
+            pending: Default::default(),
         }
     }
-}
 
-impl Session {
-    pub fn with_connection(connection: hannibal::Sender<message::FromSession>) -> Self {
+    pub fn with_connection(connection: hannibal::Sender<message::FromSession>, identity: Identity) -> Self {
         Session {
             connection: Some(connection),
-            ..Default::default()
+            ..Session::new(identity)
         }
     }
 
-    pub async fn dispatch_command(&mut self, cmd: protocol::SessionCommand, ctx: &mut Context<Self>) {
+    pub async fn dispatch_command(
+        &mut self,
+        id: Option<RequestId>,
+        cmd: protocol::SessionCommand,
+        ctx: &mut Context<Self>,
+    ) {
         log::trace!("dispatching {cmd:#?}");
         match cmd {
-            protocol::SessionCommand::Join { room } => self.join(room, ctx).await,
+            protocol::SessionCommand::Join { room, password } => self.join(id, room, password, ctx).await,
+            protocol::SessionCommand::CreateRoom { room, settings } => self.create_room(id, room, settings, ctx).await,
             protocol::SessionCommand::ChatRoom { room, command } => self.send_to_room(
+                id,
                 room,
                 ChatRoomCommand {
                     command,
                     session_id: self.session_id,
+                    session: ctx.address().downgrade(),
+                    request: id,
                 },
             ),
-            protocol::SessionCommand::ListRooms => todo!(),
-            protocol::SessionCommand::ListMyRooms => todo!(),
-            protocol::SessionCommand::ShutDown => todo!(),
-            protocol::SessionCommand::Authenticate { .. } => todo!(),
+            protocol::SessionCommand::ListRooms => self.list_rooms(id).await,
+            protocol::SessionCommand::ListMyRooms => self.list_my_rooms(id).await,
+            protocol::SessionCommand::ShutDown => self.shut_down(id),
+            protocol::SessionCommand::Direct { to, payload } => self.send_direct(id, to.into(), payload).await,
+            protocol::SessionCommand::CloseRoom { room } => self.close_room(id, room).await,
+            protocol::SessionCommand::SetPresence { presence } => {
+                self.presence = presence;
+                self.publish_presence(self.presence.clone());
+            }
+            protocol::SessionCommand::SubscribePresence { username } => {
+                self.subscribe_presence(id, username, true).await
+            }
+            protocol::SessionCommand::UnsubscribePresence { username } => {
+                self.subscribe_presence(id, username, false).await
+            }
+            protocol::SessionCommand::Authenticate { .. } => {
+                self.send_error(id, ErrorCode::UnsupportedCommand, "already authenticated")
+            }
         }
     }
 
-    pub async fn join(&self, room_id: RoomId, ctx: &mut Context<Self>) {
+    fn as_participant(&self, ctx: &mut Context<Self>) -> RoomParticipant {
+        RoomParticipant {
+            session_id: self.session_id,
+            addr: ctx.address().downgrade(),
+            username: self.username.clone(),
+            profile: self.profile.clone(),
+            role: protocol::Role::Member,
+            muted: false,
+        }
+    }
+
+    pub async fn join(
+        &mut self,
+        id: Option<RequestId>,
+        room_id: RoomId,
+        password: Option<String>,
+        ctx: &mut Context<Self>,
+    ) {
         log::debug!("join {room_id}");
         let msg = room_manager::Command::JoinRoom {
             room_id,
-            participant: RoomParticipant {
-                session_id: self.session_id,
-                addr: ctx.address().downgrade(),
-                profile: self.session_id.to_string(), // self.profile.clone(),
-            },
+            participant: self.as_participant(ctx),
+            password,
+            request: id,
             // return_addr: ctx.address().recipient(),
         };
 
-        let rm = RoomManager::from_registry().await.unwrap();
-        if let Err(error) = rm.send(msg) {
-            log::error!("can't join room {error}")
+        let joined = async { RoomManager::from_registry().await?.send(msg) };
+        if let Err(error) = joined.await {
+            log::error!("can't join room {error}");
+            self.send_error(id, ErrorCode::Internal, "can't join room");
+        }
+    }
+
+    pub async fn create_room(
+        &mut self,
+        id: Option<RequestId>,
+        room_id: RoomId,
+        settings: protocol::RoomSettings,
+        ctx: &mut Context<Self>,
+    ) {
+        log::debug!("create {room_id}");
+        let msg = room_manager::command::CreateRoom {
+            room_id: room_id.clone(),
+            settings,
+            participant: self.as_participant(ctx),
+        };
+
+        let created = async { RoomManager::from_registry().await?.call(msg).await };
+        match created.await {
+            Ok(Ok(())) => self.reply(id, protocol::SessionMessage::RoomCreated { room: room_id }),
+            Ok(Err(code)) => self.send_error(id, code, format!("can't create {room_id}")),
+            Err(error) => {
+                log::error!("can't create room {error}");
+                self.send_error(id, ErrorCode::Internal, "can't create room");
+            }
+        }
+    }
+
+    pub async fn close_room(&mut self, id: Option<RequestId>, room_id: RoomId) {
+        let msg = room_manager::command::CloseRoom {
+            room_id: room_id.clone(),
+            session_id: self.session_id,
+            username: self.username.clone(),
+            is_admin: self.is_admin,
+        };
+        let closed = async { RoomManager::from_registry().await?.call(msg).await };
+        match closed.await {
+            Ok(Ok(())) => {
+                self.rooms.remove(&room_id);
+                self.reply(id, protocol::SessionMessage::RoomClosed { room: room_id });
+            }
+            Ok(Err(code)) => self.send_error(id, code, format!("can't close {room_id}")),
+            Err(error) => {
+                log::error!("can't close room {error}");
+                self.send_error(id, ErrorCode::Internal, "can't close room");
+            }
+        }
+    }
+
+    pub async fn list_rooms(&mut self, id: Option<RequestId>) {
+        match RoomManager::get_room_list().await {
+            Ok(rooms) => self.reply(id, protocol::SessionMessage::RoomList { rooms }),
+            Err(error) => {
+                log::error!("can't list rooms {error}");
+                self.send_error(id, ErrorCode::Internal, "can't list rooms");
+            }
         }
     }
 
-    pub fn send_to_connection(&self, message: FromSession) {
-        if let Some(ref connection) = self.connection {
-            if connection.can_upgrade() {
-                if let Err(e) = connection.send(message) {
-                    log::warn!("failed to send to connection {}", e);
+    /// with the number of unread messages in each room
+    pub async fn list_my_rooms(&mut self, id: Option<RequestId>) {
+        let mut rooms = Vec::new();
+        for (room_id, room) in &self.rooms {
+            if let Some(room) = room.upgrade() {
+                let unread = room
+                    .call(GetUnread {
+                        username: self.username.clone(),
+                    })
+                    .await;
+                match unread {
+                    Ok(unread) => rooms.push(protocol::MyRoom {
+                        room: room_id.clone(),
+                        unread,
+                    }),
+                    Err(error) => log::warn!("failed to count unread messages in {room_id} {error}"),
                 }
-            } else {
-                log::warn!("connection can't upgrade");
             }
+        }
+        self.reply(id, protocol::SessionMessage::MyRoomList { rooms });
+    }
+
+    pub fn shut_down(&mut self, id: Option<RequestId>) {
+        if self.is_admin {
+            log::info!("{} requested shutdown", self.profile.full_name);
+            let reason = format!("shutdown requested by {}", self.profile.full_name);
+            async_std::task::spawn(shutdown::shut_down(reason));
+        } else {
+            log::warn!("{} is not allowed to shut down the server", self.profile.full_name);
+            self.send_error(id, ErrorCode::Forbidden, "not allowed to shut down the server");
+        }
+    }
+
+    pub async fn send_direct(&mut self, id: Option<RequestId>, to: SessionId, payload: serde_json::Value) {
+        let msg = session_manager::command::SendDirect {
+            from: self.session_id,
+            to,
+            payload,
+        };
+        let delivered = async { SessionManager::from_registry().await?.call(msg).await };
+        match delivered.await {
+            Ok(true) => self.reply(id, protocol::SessionMessage::Delivered { to: to.into() }),
+            Ok(false) => self.reply(id, protocol::SessionMessage::Undelivered { to: to.into() }),
+            Err(error) => {
+                log::error!("can't send direct message {error}");
+                self.send_error(id, ErrorCode::Internal, "can't send direct message");
+            }
+        }
+    }
+
+    pub fn reconnect(
+        &mut self,
+        connection: hannibal::Sender<message::FromSession>,
+        token: Uuid,
+        request: Option<RequestId>,
+        ctx: &mut Context<Self>,
+    ) {
+        log::debug!("resuming session {} on new connection", self.session_id);
+        self.connection = Some(connection);
+        self.last_seen_connected = Instant::now();
+        self.send_to_connection(FromSession::SessionAssociated {
+            session: ctx.address().downgrade(),
+        });
+        self.send_to_connection(FromSession::reply(
+            request,
+            protocol::SessionMessage::Authenticated { token },
+        ));
+        self.send_to_connection(
+            protocol::SessionMessage::Profile {
+                profile: self.profile.clone(),
+            }
+            .into(),
+        );
+        self.flush_pending();
+        self.publish_presence(self.presence.clone());
+    }
+
+    /// tells the rooms of this session and the subscribers of its user, unless nothing changed
+    pub fn publish_presence(&mut self, presence: protocol::Presence) {
+        if self.published_presence.as_ref() == Some(&presence) {
+            return;
+        }
+        log::trace!("{} is {presence:?}", self.username);
+        self.published_presence = Some(presence.clone());
+        let update = command::PresenceChanged {
+            session_id: self.session_id,
+            username: self.username.clone(),
+            presence,
+        };
+        for (room_id, room) in &self.rooms {
+            if let Some(room) = room.upgrade() {
+                if let Err(error) = room.send(update.clone()) {
+                    log::warn!("failed to publish presence to {room_id} {error}");
+                }
+            }
+        }
+        async_std::task::spawn(async move {
+            let published = async { SessionManager::from_registry().await?.send(update) };
+            if let Err(error) = published.await {
+                log::warn!("failed to publish presence {error}");
+            }
+        });
+    }
+
+    pub async fn subscribe_presence(&mut self, id: Option<RequestId>, username: String, subscribe: bool) {
+        let msg = session_manager::command::SubscribePresence {
+            subscriber: self.session_id,
+            username,
+            subscribe,
+        };
+        let subscribed = async { SessionManager::from_registry().await?.send(msg) };
+        if let Err(error) = subscribed.await {
+            log::error!("can't subscribe to presence {error}");
+            self.send_error(id, ErrorCode::Internal, "can't subscribe to presence");
+        }
+    }
+
+    pub fn send_to_connection(&mut self, message: FromSession) {
+        if let Some(connection) = self.connection.as_ref().filter(|c| c.can_upgrade()) {
+            if let Err(e) = connection.send(message) {
+                log::warn!("failed to send to connection {}", e);
+            }
+        } else if matches!(message, FromSession::SessionMessage(_) | FromSession::Reply { .. }) {
+            log::trace!("have no connection, keeping message for later");
+            if self.pending.len() == MAX_PENDING_MESSAGES {
+                log::warn!("too many pending messages, dropping the oldest");
+                self.pending.pop_front();
+            }
+            self.pending.push_back(message);
         } else {
             log::warn!("have no connection");
         }
     }
 
-    pub fn send_to_room<C>(&self, room_id: RoomId, command: C)
+    /// replays everything that was missed while disconnected
+    fn flush_pending(&mut self) {
+        if !self.pending.is_empty() {
+            log::debug!("replaying {} pending messages", self.pending.len());
+        }
+        for message in std::mem::take(&mut self.pending) {
+            self.send_to_connection(message);
+        }
+    }
+
+    /// tells every room this session is gone, so they can update their rosters
+    pub fn leave_all_rooms(&mut self, ctx: &mut Context<Self>) {
+        for (room_id, room) in self.rooms.drain() {
+            if let Some(room) = room.upgrade() {
+                log::trace!("leaving {room_id}");
+                if let Err(error) = room.send(ChatRoomCommand {
+                    command: protocol::ChatRoomCommand::Leave,
+                    session_id: self.session_id,
+                    session: ctx.address().downgrade(),
+                    request: None,
+                }) {
+                    log::warn!("failed to leave {room_id} {error}");
+                }
+            }
+        }
+    }
+
+    /// direct reply to the request with `id`
+    pub fn reply(&mut self, id: Option<RequestId>, message: protocol::SessionMessage) {
+        self.send_to_connection(FromSession::reply(id, message));
+    }
+
+    pub fn send_error(&mut self, id: Option<RequestId>, code: ErrorCode, message: impl Into<String>) {
+        self.reply(id, protocol::SessionMessage::err(code, message));
+    }
+
+    pub fn send_to_room<C>(&mut self, id: Option<RequestId>, room_id: RoomId, command: C)
     where
         C: hannibal::Message<Result = ()> + Send + 'static,
         crate::room::Room: hannibal::Handler<C>,
     {
-        if let Some(room) = self.rooms.get(&room_id).and_then(WeakAddr::upgrade) {
-            room.send(command).unwrap();
+        let room = self.rooms.get(&room_id).or_else(|| self.waiting.get(&room_id));
+        match room.map(WeakAddr::upgrade) {
+            Some(Some(room)) => {
+                if let Err(error) = room.send(command) {
+                    log::error!("failed to send to {room_id} {error}");
+                    self.send_error(id, ErrorCode::RoomNotFound, format!("{room_id} is gone"));
+                }
+            }
+            Some(None) => {
+                self.rooms.remove(&room_id);
+                self.waiting.remove(&room_id);
+                self.send_error(id, ErrorCode::RoomNotFound, format!("{room_id} is gone"));
+            }
+            None => self.send_error(id, ErrorCode::NotAMember, format!("not a member of {room_id}")),
         }
     }
 }
@@ -123,7 +417,8 @@ impl Session {
         if let Some(can_upgrade) = self.connection.as_ref().map(|c| c.can_upgrade()) {
             if !can_upgrade {
                 log::trace!("connection is gone");
-                self.connection = None
+                self.connection = None;
+                self.publish_presence(protocol::Presence::Offline);
             } else {
                 // I'm still alive, updating timestamp
                 self.last_seen_connected = Instant::now();
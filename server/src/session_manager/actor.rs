@@ -2,9 +2,10 @@ use std::time::Duration;
 
 use async_trait::async_trait;
 use hannibal::{Actor, Context, Handler};
+use signaler_protocol::Credentials;
 use tracing::log;
 
-use crate::metrics::MetricsService;
+use crate::{metrics::MetricsService, session::command::PresenceChanged};
 
 use super::{command::*, SessionManager};
 
@@ -22,6 +23,7 @@ impl Actor for SessionManager {
     }
     async fn stopped(&mut self, _ctx: &mut hannibal::Context<Self>) {
         log::trace!("shutting down SessionManager");
+        self.stop_sessions().await;
     }
 }
 
@@ -29,21 +31,56 @@ impl Actor for SessionManager {
 impl Handler<Command> for SessionManager {
     async fn handle(&mut self, _ctx: &mut Context<Self>, cmd: Command) {
         match cmd {
+            Command::AssociateConnection {
+                credentials: Credentials::Token { token },
+                connection,
+                request,
+            } => {
+                if let Err(error) = self.resume_session(token, connection, request) {
+                    log::error!("failed to resume {}", error);
+                }
+            }
             Command::AssociateConnection {
                 credentials,
                 connection,
+                request,
             } => {
-                if let Err(error) = self.create_session(&credentials, connection).await {
+                if let Err(error) = self.create_session(&credentials, connection, request).await {
                     log::error!("failed to associate {}", error);
                 } else if let Some(gauge) = self.open_sessions.as_ref() {
                     gauge.inc();
                     log::trace!("increasing sessions count {:?}", gauge.get());
                 }
             }
+            Command::UseCredentialStore(store) => {
+                log::debug!("using new credential store");
+                self.credential_store = Some(store);
+            }
         }
     }
 }
 
+#[async_trait]
+impl Handler<SendDirect> for SessionManager {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: SendDirect) -> bool {
+        self.send_direct(msg.from, msg.to, msg.payload)
+    }
+}
+
+#[async_trait]
+impl Handler<PresenceChanged> for SessionManager {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: PresenceChanged) {
+        self.publish_presence(msg);
+    }
+}
+
+#[async_trait]
+impl Handler<SubscribePresence> for SessionManager {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: SubscribePresence) {
+        self.subscribe_presence(msg);
+    }
+}
+
 #[async_trait::async_trait]
 impl Handler<Gc> for SessionManager {
     async fn handle(&mut self, ctx: &mut hannibal::Context<Self>, _: Gc) {
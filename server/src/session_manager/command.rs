@@ -1,14 +1,37 @@
 use hannibal::{message, WeakAddr};
-use signaler_protocol::Credentials;
+use signaler_protocol::{Credentials, RequestId};
 
-use crate::connection::Connection;
+use crate::{connection::Connection, credentials::CredentialStore, session::SessionId};
 
 #[message]
 pub enum Command {
     AssociateConnection {
         connection: WeakAddr<Connection>,
         credentials: Credentials,
+        /// id of the `Authenticate` request, echoed on the reply
+        request: Option<RequestId>,
     },
+    UseCredentialStore(Box<dyn CredentialStore>),
+}
+
+/// routes a `SessionCommand::Direct` to the session `to`
+///
+/// results in `false` if `to` does not exist (anymore)
+#[message(result = "bool")]
+#[derive(Debug)]
+pub struct SendDirect {
+    pub from: SessionId,
+    pub to: SessionId,
+    pub payload: serde_json::Value,
+}
+
+/// start or stop passing `PresenceChanged` of `username` on to `subscriber`
+#[message]
+#[derive(Debug)]
+pub struct SubscribePresence {
+    pub subscriber: SessionId,
+    pub username: String,
+    pub subscribe: bool,
 }
 
 #[message]
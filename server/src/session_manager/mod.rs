@@ -1,12 +1,17 @@
-use std::{collections::HashMap, fmt};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
 use hannibal::{Actor, Addr, Context, WeakAddr};
 use prometheus::IntGauge;
-use signaler_protocol::Credentials;
+use signaler_protocol::{Credentials, Presence, RequestId, SessionMessage};
 use tracing::log;
+use uuid::Uuid;
 
 use crate::{
     connection::Connection,
+    credentials::{self, CredentialStore, Identity},
     session::{self, Session, SessionId},
 };
 
@@ -16,7 +21,14 @@ pub mod command;
 #[derive(Default)]
 pub struct SessionManager {
     sessions: HashMap<SessionId, Addr<Session>>,
+    /// resume tokens handed out to clients
+    tokens: HashMap<Uuid, SessionId>,
     open_sessions: Option<IntGauge>,
+    credential_store: Option<Box<dyn CredentialStore>>,
+    /// sessions that want to know about the presence of a user
+    presence_subscribers: HashMap<String, HashSet<SessionId>>,
+    /// latest presence of every session that is not offline, by username
+    presences: HashMap<String, HashMap<SessionId, Presence>>,
 }
 
 impl fmt::Debug for SessionManager {
@@ -30,18 +42,38 @@ impl fmt::Debug for SessionManager {
 impl SessionManager {
     pub async fn create_session(
         &mut self,
-        _credentials: &Credentials,
+        credentials: &Credentials,
         // connection: Sender<command::SessionAssociated>,
         connection: WeakAddr<Connection>,
+        request: Option<RequestId>,
     ) -> Result<(), anyhow::Error> {
         if let Some(connection) = connection.upgrade() {
-            let session = Session::with_connection(connection.sender());
+            let identity = match self.authenticate(credentials) {
+                Ok(identity) => identity,
+                Err(error) => {
+                    connection.send(session::message::FromSession::reply(
+                        request,
+                        SessionMessage::AuthenticationFailed,
+                    ))?;
+                    return Err(error.into());
+                }
+            };
+            log::debug!("authenticated {:?}", identity.username);
+
+            let profile = identity.profile.clone();
+            let session = Session::with_connection(connection.sender(), identity);
             let session_id = session.session_id;
             let session_addr = session.start().await?;
             let session_weak = session_addr.downgrade();
             self.sessions.insert(session_id, session_addr);
+            let token = self.issue_token(session_id);
 
             connection.send(session::message::FromSession::SessionAssociated { session: session_weak })?;
+            connection.send(session::message::FromSession::reply(
+                request,
+                SessionMessage::Authenticated { token },
+            ))?;
+            connection.send(session::message::FromSession::from(SessionMessage::Profile { profile }))?;
         } else {
             anyhow::bail!("connection is already dead")
         }
@@ -49,6 +81,160 @@ impl SessionManager {
         Ok(())
     }
 
+    /// rebinds the session behind `token` to a new connection
+    ///
+    /// tokens are single use, the session hands a fresh one to the client
+    pub fn resume_session(
+        &mut self,
+        token: Uuid,
+        connection: WeakAddr<Connection>,
+        request: Option<RequestId>,
+    ) -> Result<(), anyhow::Error> {
+        if let Some(connection) = connection.upgrade() {
+            let session = self
+                .tokens
+                .remove(&token)
+                .and_then(|session_id| {
+                    self.sessions
+                        .get(&session_id)
+                        .map(|session| (session_id, session.clone()))
+                })
+                .filter(|(_, session)| !session.stopped());
+
+            if let Some((session_id, session)) = session {
+                log::debug!("resuming session {}", session_id);
+                let token = self.issue_token(session_id);
+                session.send(session::command::Reconnect {
+                    connection: connection.sender(),
+                    token,
+                    request,
+                })?;
+            } else {
+                connection.send(session::message::FromSession::reply(
+                    request,
+                    SessionMessage::AuthenticationFailed,
+                ))?;
+                anyhow::bail!("no session to resume")
+            }
+        } else {
+            anyhow::bail!("connection is already dead")
+        }
+
+        Ok(())
+    }
+
+    fn send_direct(&self, from: SessionId, to: SessionId, payload: serde_json::Value) -> bool {
+        match self.sessions.get(&to).filter(|session| !session.stopped()) {
+            Some(session) => {
+                log::trace!("routing direct message from {from} to {to}");
+                if let Err(error) = session.send(session::command::Direct { from, payload }) {
+                    log::warn!("failed to send Direct to {to} {error}");
+                    return false;
+                }
+                true
+            }
+            None => {
+                log::debug!("can't route direct message from {from}, {to} is gone");
+                false
+            }
+        }
+    }
+
+    /// a user only goes offline once their last session does,
+    /// until then subscribers see the presence of one of the remaining sessions
+    fn publish_presence(&mut self, update: session::command::PresenceChanged) {
+        let update = if update.presence == Presence::Offline {
+            let sessions = match self.presences.get_mut(&update.username) {
+                Some(sessions) if sessions.remove(&update.session_id).is_some() => sessions,
+                _ => {
+                    log::trace!("{} was already offline", update.session_id);
+                    return;
+                }
+            };
+            let remaining = sessions
+                .iter()
+                .next()
+                .map(|(session_id, presence)| (*session_id, presence.clone()));
+            match remaining {
+                Some((session_id, presence)) => session::command::PresenceChanged {
+                    session_id,
+                    presence,
+                    ..update
+                },
+                None => {
+                    self.presences.remove(&update.username);
+                    update
+                }
+            }
+        } else {
+            self.presences
+                .entry(update.username.clone())
+                .or_default()
+                .insert(update.session_id, update.presence.clone());
+            update
+        };
+        let subscribers = self.presence_subscribers.get(&update.username).into_iter().flatten();
+        for session in subscribers.filter_map(|subscriber| self.sessions.get(subscriber)) {
+            if let Err(error) = session.send(update.clone()) {
+                log::warn!("failed to send presence of {} {error}", update.username);
+            }
+        }
+    }
+
+    /// new subscribers immediately learn the current presence
+    fn subscribe_presence(&mut self, msg: command::SubscribePresence) {
+        if !msg.subscribe {
+            if let Some(subscribers) = self.presence_subscribers.get_mut(&msg.username) {
+                subscribers.remove(&msg.subscriber);
+            }
+            return;
+        }
+        log::trace!("{} subscribes to the presence of {}", msg.subscriber, msg.username);
+        self.presence_subscribers
+            .entry(msg.username.clone())
+            .or_default()
+            .insert(msg.subscriber);
+        let session = match self.sessions.get(&msg.subscriber) {
+            Some(session) => session,
+            None => return,
+        };
+        for (session_id, presence) in self.presences.get(&msg.username).into_iter().flatten() {
+            let current = session::command::PresenceChanged {
+                session_id: *session_id,
+                username: msg.username.clone(),
+                presence: presence.clone(),
+            };
+            if let Err(error) = session.send(current) {
+                log::warn!("failed to send presence of {} {error}", msg.username);
+            }
+        }
+    }
+
+    fn issue_token(&mut self, session_id: SessionId) -> Uuid {
+        let token = Uuid::new_v4();
+        self.tokens.insert(token, session_id);
+        token
+    }
+
+    fn authenticate(&self, credentials: &Credentials) -> Result<Identity, credentials::Error> {
+        self.credential_store
+            .as_ref()
+            .ok_or(credentials::Error::NoStore)?
+            .authenticate(credentials)
+    }
+
+    async fn stop_sessions(&mut self) {
+        for (id, mut session) in self.sessions.drain() {
+            log::trace!("stopping session {}", id);
+            if let Err(error) = session.stop(None) {
+                log::warn!("failed to stop session {} {}", id, error);
+            } else {
+                session.wait_for_stop().await;
+            }
+        }
+        self.tokens.clear();
+    }
+
     fn gc(&mut self, _ctx: &mut Context<Self>) {
         // log::trace!("gc");
         self.sessions.retain(|id, session| {
@@ -63,5 +249,26 @@ impl SessionManager {
                 true
             }
         });
+        self.tokens
+            .retain(|_, session_id| self.sessions.contains_key(session_id));
+        for subscribers in self.presence_subscribers.values_mut() {
+            subscribers.retain(|session_id| self.sessions.contains_key(session_id));
+        }
+        self.presence_subscribers
+            .retain(|_, subscribers| !subscribers.is_empty());
+        let gone = self
+            .presences
+            .iter()
+            .flat_map(|(username, sessions)| sessions.keys().map(move |session_id| (username, session_id)))
+            .filter(|(_, session_id)| !self.sessions.contains_key(session_id))
+            .map(|(username, session_id)| session::command::PresenceChanged {
+                session_id: *session_id,
+                username: username.clone(),
+                presence: Presence::Offline,
+            })
+            .collect::<Vec<_>>();
+        for update in gone {
+            self.publish_presence(update);
+        }
     }
 }
@@ -0,0 +1,85 @@
+//! Graceful shutdown
+//!
+//! triggered either by SIGINT/SIGTERM or by an admin sending `SessionCommand::ShutDown`
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use hannibal::{Broker, Service};
+use tracing::log;
+
+use crate::{
+    metrics::MetricsService,
+    room_manager::RoomManager,
+    session_manager::SessionManager,
+    web_server::{self, WebServer},
+};
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// published to every [`Connection`](crate::connection::Connection) before the server goes away
+#[hannibal::message]
+#[derive(Clone, Debug)]
+pub struct GoingAway {
+    pub reason: String,
+}
+
+/// resolves once the process receives SIGINT or SIGTERM
+#[cfg(unix)]
+pub async fn signal() -> std::io::Result<()> {
+    use futures::{future, FutureExt};
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    future::select(terminate.recv().boxed(), interrupt.recv().boxed()).await;
+    Ok(())
+}
+
+/// resolves once the process receives SIGINT
+#[cfg(not(unix))]
+pub async fn signal() -> std::io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
+
+/// stops all services in order
+///
+/// 1. stop accepting new connections
+/// 2. tell every connection that we're going away and wait for them to close
+/// 3. stop all sessions
+/// 4. drain and stop all rooms
+/// 5. stop the web server, which lets `main` return
+pub async fn shut_down(reason: String) {
+    if SHUTTING_DOWN.swap(true, Ordering::SeqCst) {
+        log::debug!("already shutting down");
+        return;
+    }
+    log::info!("shutting down: {reason}");
+    if let Err(error) = stop_services(reason).await {
+        log::error!("failed to shut down gracefully {error}");
+    }
+}
+
+async fn stop_services(reason: String) -> hannibal::Result<()> {
+    let mut web_server = WebServer::from_registry().await?;
+    web_server.call(web_server::StopListening).await?;
+
+    Broker::<GoingAway>::from_registry()
+        .await?
+        .publish(GoingAway { reason })?;
+    web_server.call(web_server::WaitForConnections).await?;
+
+    let mut session_manager = SessionManager::from_registry().await?;
+    session_manager.stop(None)?;
+    session_manager.wait_for_stop().await;
+
+    let mut room_manager = RoomManager::from_registry().await?;
+    room_manager.stop(None)?;
+    room_manager.wait_for_stop().await;
+
+    let mut metrics = MetricsService::from_registry().await?;
+    metrics.stop(None)?;
+    metrics.wait_for_stop().await;
+
+    web_server.stop(None)?;
+    Ok(())
+}
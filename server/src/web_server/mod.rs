@@ -3,8 +3,22 @@ use std::net::SocketAddr;
 
 pub use self::warp::*;
 
-#[hannibal::message]
+#[hannibal::message(result = "hannibal::Result<()>")]
 #[derive(Debug)]
 pub struct Listen {
     pub socket: SocketAddr,
 }
+
+/// stops accepting new connections, resolves once the server has terminated
+#[hannibal::message]
+#[derive(Debug)]
+pub struct StopListening;
+
+/// a websocket connection was established
+#[hannibal::message]
+pub struct Connected(pub hannibal::WeakAddr<crate::connection::Connection>);
+
+/// resolves once every connection has closed, or after a timeout
+#[hannibal::message]
+#[derive(Debug)]
+pub struct WaitForConnections;
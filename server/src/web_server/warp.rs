@@ -4,21 +4,40 @@ use prometheus::{Encoder, TextEncoder};
 use warp::{http::Uri, ws::WebSocket, Filter};
 use warp_prometheus::Metrics;
 
-use hannibal::{Actor, Context, Handler, Service};
+use async_std::task::JoinHandle;
+use futures::channel::oneshot;
+use hannibal::{Actor, Context, Handler, Service, WeakAddr};
 
-use std::{net::SocketAddr, path::PathBuf};
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
 
-use crate::metrics::MetricsService;
+use crate::{connection::Connection, metrics::MetricsService};
+
+/// how long to wait for connections to close when shutting down
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub async fn peer_connected(ws: WebSocket /*, broker: Broker*/) {
     log::debug!("user connected{:#?}", ws);
-    let connection = crate::connection::Connection::new(ws);
+    let connection = Connection::new(ws);
     let addr = hannibal::Actor::start(connection).await.unwrap();
+    let tracked = async {
+        WebServer::from_registry()
+            .await?
+            .send(super::Connected(addr.downgrade()))
+    };
+    if let Err(error) = tracked.await {
+        log::warn!("failed to track connection {error}");
+    }
     addr.wait_for_stop().await
 }
 
 #[derive(Default)]
-pub struct WebServer;
+pub struct WebServer {
+    /// triggers the graceful shutdown of the running server
+    stop_listening: Option<oneshot::Sender<()>>,
+    serving: Option<JoinHandle<()>>,
+    /// websockets outlive the server, so they are waited for separately
+    connections: Vec<WeakAddr<Connection>>,
+}
 
 #[async_trait::async_trait]
 impl Actor for WebServer {
@@ -34,10 +53,44 @@ impl Service for WebServer {} // TODO: services aren't even supervised
 
 #[async_trait::async_trait]
 impl Handler<super::Listen> for WebServer {
-    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: super::Listen) {
-        if let Err(error) = self.start(msg.socket).await {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: super::Listen) -> hannibal::Result<()> {
+        let result = self.start(msg.socket).await;
+        if let Err(ref error) = result {
             log::error!("{}", error);
         }
+        result
+    }
+}
+
+#[async_trait::async_trait]
+impl Handler<super::StopListening> for WebServer {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _: super::StopListening) {
+        if let Some(stop_listening) = self.stop_listening.take() {
+            log::info!("no longer accepting connections");
+            let _ = stop_listening.send(());
+        }
+        if let Some(serving) = self.serving.take() {
+            serving.await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Handler<super::Connected> for WebServer {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, msg: super::Connected) {
+        self.connections.retain(|connection| connection.upgrade().is_some());
+        self.connections.push(msg.0);
+    }
+}
+
+#[async_trait::async_trait]
+impl Handler<super::WaitForConnections> for WebServer {
+    async fn handle(&mut self, _ctx: &mut Context<Self>, _: super::WaitForConnections) {
+        let open = self.connections.drain(..).filter_map(|connection| connection.upgrade());
+        let closed = futures::future::join_all(open.map(|connection| connection.wait_for_stop()));
+        if async_std::future::timeout(CLOSE_TIMEOUT, closed).await.is_err() {
+            log::warn!("connections did not close within {CLOSE_TIMEOUT:?}");
+        }
     }
 }
 
@@ -84,10 +137,14 @@ impl WebServer {
 
         let dummy_listener = std::net::TcpListener::bind(addr);
         match dummy_listener {
-            Err(error) => log::error!("cannot bind {} because {}", addr, error),
+            Err(error) => {
+                log::error!("cannot bind {} because {}", addr, error);
+                return Err(error.into());
+            }
             Ok(dummy_listener) => {
                 std::mem::drop(dummy_listener);
-                warp::serve(
+                let (stop_listening, stopped_listening) = oneshot::channel::<()>();
+                let (_, server) = warp::serve(
                     routes.with(warp::log::custom(|info| {
                         log::trace!(
                             "{} {} {} {:?}",
@@ -98,11 +155,17 @@ impl WebServer {
                         )
                     })), //.with(warp::log::custom(move |log| metrics.http_metrics(log))),
                 )
-                .run(addr)
-                .await;
+                .bind_with_graceful_shutdown(addr, async move {
+                    let _ = stopped_listening.await;
+                });
+
+                self.stop_listening = Some(stop_listening);
+                self.serving = Some(async_std::task::spawn(async move {
+                    server.await;
+                    log::info!("web server has terminated");
+                }));
             }
         }
-        log::info!("web server has terminated");
         Ok(())
     }
 }